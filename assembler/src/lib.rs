@@ -0,0 +1,262 @@
+use common::{
+    code::{self, OperandKind},
+    error::NovaError,
+};
+use std::collections::HashMap;
+
+pub fn new() -> Assembler {
+    Assembler {
+        native_functions: common::table::new(),
+    }
+}
+
+/// Turns the text printed by `disassembler::render_asm` back into bytecode.
+/// One instruction per line, an opcode name from `Code` followed by its
+/// operand. `name:` defines a label that jumps may use in place of a
+/// distance, indentation is ignored and `;` starts a comment.
+pub struct Assembler {
+    pub native_functions: common::table::Table<String>,
+}
+
+enum Argument {
+    None,
+    Uint(usize),
+    Label(String),
+    Int(i64),
+    Byte(u8),
+    Float(f64),
+    Char(char),
+    String(String),
+}
+
+enum Item {
+    Label(String),
+    Instruction {
+        line: usize,
+        opcode: u8,
+        argument: Argument,
+    },
+}
+
+fn line_error(line: usize, msg: String) -> NovaError {
+    common::error::file_error(format!("Line {}: {}", line, msg))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(open), c) if c == open => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ';') => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+// reverses the escapes `{:?}` writes for strings and chars
+fn unquote(text: &str, quote: char) -> Option<String> {
+    let inner = text.strip_prefix(quote)?.strip_suffix(quote)?;
+    let mut output = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => output.push('\n'),
+            'r' => output.push('\r'),
+            't' => output.push('\t'),
+            '0' => output.push('\0'),
+            '\\' => output.push('\\'),
+            '"' => output.push('"'),
+            '\'' => output.push('\''),
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let close = rest.find('}')?;
+                let scalar = u32::from_str_radix(&rest[..close], 16).ok()?;
+                output.push(char::from_u32(scalar)?);
+                chars = rest[close + 1..].chars();
+            }
+            _ => return None,
+        }
+    }
+    Some(output)
+}
+
+impl Assembler {
+    fn native(&mut self, name: &str) -> usize {
+        match self.native_functions.get_index(name.to_string()) {
+            Some(index) => index,
+            None => {
+                self.native_functions.insert(name.to_string());
+                self.native_functions.len() - 1
+            }
+        }
+    }
+
+    fn argument(&mut self, line: usize, opcode: u8, operand: &str) -> Result<Argument, NovaError> {
+        let name = code::name(opcode).unwrap_or_default();
+        let kind = code::operand_kind(opcode);
+        if operand.is_empty() {
+            return match kind {
+                OperandKind::None => Ok(Argument::None),
+                _ => Err(line_error(line, format!("{} needs an operand", name))),
+            };
+        }
+        let invalid = || line_error(line, format!("Invalid operand {} for {}", operand, name));
+        Ok(match kind {
+            OperandKind::None => {
+                return Err(line_error(line, format!("{} takes no operand", name)));
+            }
+            OperandKind::Uint => match operand.parse() {
+                Ok(uint) => Argument::Uint(uint),
+                Err(_) if opcode == code::Code::NATIVE => Argument::Uint(self.native(operand)),
+                Err(_) => return Err(invalid()),
+            },
+            OperandKind::Jump => match operand.parse() {
+                Ok(jump) => Argument::Uint(jump),
+                Err(_) => Argument::Label(operand.to_string()),
+            },
+            OperandKind::Int => Argument::Int(operand.parse().map_err(|_| invalid())?),
+            OperandKind::Byte => Argument::Byte(operand.parse().map_err(|_| invalid())?),
+            OperandKind::Float => Argument::Float(operand.parse().map_err(|_| invalid())?),
+            OperandKind::Char => {
+                let text = unquote(operand, '\'').ok_or_else(invalid)?;
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Argument::Char(c),
+                    _ => return Err(invalid()),
+                }
+            }
+            OperandKind::String => Argument::String(unquote(operand, '"').ok_or_else(invalid)?),
+        })
+    }
+
+    fn parse(&mut self, input: &str) -> Result<Vec<Item>, NovaError> {
+        let mut items = vec![];
+        let mut labels = vec![];
+        for (index, text) in input.lines().enumerate() {
+            let line = index + 1;
+            let text = strip_comment(text).trim();
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(label) = text.strip_suffix(':') {
+                if label.is_empty() || label.contains(char::is_whitespace) {
+                    return Err(line_error(line, format!("Invalid label {}", text)));
+                }
+                if labels.contains(&label) {
+                    return Err(line_error(
+                        line,
+                        format!("Label {} is defined twice", label),
+                    ));
+                }
+                labels.push(label);
+                items.push(Item::Label(label.to_string()));
+                continue;
+            }
+            let (name, operand) = match text.split_once(char::is_whitespace) {
+                Some((name, operand)) => (name, operand.trim()),
+                None => (text, ""),
+            };
+            let opcode = match code::opcode(name) {
+                Some(opcode) => opcode,
+                None => return Err(line_error(line, format!("Unknown opcode {}", name))),
+            };
+            let argument = self.argument(line, opcode, operand)?;
+            items.push(Item::Instruction {
+                line,
+                opcode,
+                argument,
+            })
+        }
+        Ok(items)
+    }
+
+    /// Assembles `input` into bytecode that `vm::Vm` can run. Native
+    /// functions named in the text are looked up in `native_functions` and
+    /// added to it when missing.
+    pub fn asm(&mut self, input: &str) -> Result<Vec<u8>, NovaError> {
+        let items = self.parse(input)?;
+
+        // jump distances are varints, so a longer jump can move every label
+        // after it. lay the program out again until no distance changes.
+        let mut jumps = vec![0; items.len()];
+        loop {
+            let mut output = vec![];
+            let mut labels = HashMap::new();
+            let mut ends = vec![0; items.len()];
+            for (index, item) in items.iter().enumerate() {
+                match item {
+                    Item::Label(label) => {
+                        labels.insert(label.as_str(), output.len());
+                    }
+                    Item::Instruction {
+                        opcode, argument, ..
+                    } => {
+                        output.push(*opcode);
+                        match argument {
+                            Argument::None => {}
+                            Argument::Uint(uint) => code::write_uint(&mut output, *uint),
+                            Argument::Label(_) => code::write_uint(&mut output, jumps[index]),
+                            Argument::Int(int) => code::write_int(&mut output, *int),
+                            Argument::Byte(byte) => output.push(*byte),
+                            Argument::Float(float) => code::write_float(&mut output, *float),
+                            Argument::Char(c) => code::write_char(&mut output, *c),
+                            Argument::String(string) => {
+                                code::write_uint(&mut output, string.len());
+                                output.extend_from_slice(string.as_bytes())
+                            }
+                        }
+                        ends[index] = output.len();
+                    }
+                }
+            }
+
+            let mut settled = true;
+            for (index, item) in items.iter().enumerate() {
+                if let Item::Instruction {
+                    line,
+                    argument: Argument::Label(label),
+                    ..
+                } = item
+                {
+                    let target = match labels.get(label.as_str()) {
+                        Some(target) => *target,
+                        None => {
+                            return Err(line_error(
+                                *line,
+                                format!("Label {} is not defined", label),
+                            ))
+                        }
+                    };
+                    let jump = match target.checked_sub(ends[index]) {
+                        Some(jump) => jump,
+                        None => {
+                            return Err(line_error(
+                                *line,
+                                format!(
+                                    "Label {} is behind the jump, jumps only go forward",
+                                    label
+                                ),
+                            ))
+                        }
+                    };
+                    if jumps[index] != jump {
+                        jumps[index] = jump;
+                        settled = false;
+                    }
+                }
+            }
+            if settled {
+                return Ok(output);
+            }
+        }
+    }
+}
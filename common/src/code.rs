@@ -78,4 +78,298 @@ impl Code {
     pub const GETBIND: u8 = 48;
 
     pub const LOOP: u8 = 49;
+
+    pub const AND: u8 = 50;
+    pub const OR: u8 = 51;
+    pub const NOT: u8 = 52;
+
+    pub const SHORTAND: u8 = 53;
+    pub const SHORTOR: u8 = 54;
+
+    pub const NOTEQUALS: u8 = 55;
+    pub const GTE: u8 = 56;
+    pub const LTE: u8 = 57;
+
+    pub const NEWMAP: u8 = 58;
+
+    pub const GROWREG: u8 = 59;
+
+    pub const TAKEID: u8 = 60;
+}
+
+const NAMES: &[(u8, &str)] = &[
+    (Code::RET, "RET"),
+    (Code::INTEGER, "INTEGER"),
+    (Code::FLOAT, "FLOAT"),
+    (Code::ADD, "ADD"),
+    (Code::SUB, "SUB"),
+    (Code::MUL, "MUL"),
+    (Code::DIV, "DIV"),
+    (Code::STOREID, "STOREID"),
+    (Code::ID, "ID"),
+    (Code::STOREFASTID, "STOREFASTID"),
+    (Code::ASSIGN, "ASSIGN"),
+    (Code::ALLOCATEREG, "ALLOCATEREG"),
+    (Code::CALL, "CALL"),
+    (Code::BLOCK, "BLOCK"),
+    (Code::DIRECTCALL, "DIRECTCALL"),
+    (Code::NEWLIST, "NEWLIST"),
+    (Code::TRUE, "TRUE"),
+    (Code::FALSE, "FALSE"),
+    (Code::FUNCTION, "FUNCTION"),
+    (Code::GTR, "GTR"),
+    (Code::LSS, "LSS"),
+    (Code::JUMPIFFALSE, "JUMPIFFALSE"),
+    (Code::REC, "REC"),
+    (Code::IF, "IF"),
+    (Code::WHEN, "WHEN"),
+    (Code::EQUALS, "EQUALS"),
+    (Code::MODULO, "MODULO"),
+    (Code::REFID, "REFID"),
+    (Code::CLOSURE, "CLOSURE"),
+    (Code::CID, "CID"),
+    (Code::STRING, "STRING"),
+    (Code::FOR, "FOR"),
+    (Code::BOUNCE, "BOUNCE"),
+    (Code::RANGE, "RANGE"),
+    (Code::FORINT, "FORINT"),
+    (Code::BYTE, "BYTE"),
+    (Code::NATIVE, "NATIVE"),
+    (Code::STOREGLOBAL, "STOREGLOBAL"),
+    (Code::GLOBALID, "GLOBALID"),
+    (Code::ALLOCATEGLOBAL, "ALLOCATEGLOBAL"),
+    (Code::CHAR, "CHAR"),
+    (Code::POP, "POP"),
+    (Code::NEG, "NEG"),
+    (Code::BREAK, "BREAK"),
+    (Code::NEWBINDING, "NEWBINDING"),
+    (Code::POPBINDING, "POPBINDING"),
+    (Code::STOREBIND, "STOREBIND"),
+    (Code::GETBIND, "GETBIND"),
+    (Code::LOOP, "LOOP"),
+    (Code::AND, "AND"),
+    (Code::OR, "OR"),
+    (Code::NOT, "NOT"),
+    (Code::SHORTAND, "SHORTAND"),
+    (Code::SHORTOR, "SHORTOR"),
+    (Code::NOTEQUALS, "NOTEQUALS"),
+    (Code::GTE, "GTE"),
+    (Code::LTE, "LTE"),
+    (Code::NEWMAP, "NEWMAP"),
+    (Code::GROWREG, "GROWREG"),
+    (Code::TAKEID, "TAKEID"),
+];
+
+/// Name of an opcode as spelled in `Code`, None for bytes that are not one.
+pub fn name(code: u8) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|(opcode, _)| *opcode == code)
+        .map(|(_, name)| *name)
+}
+
+/// Opcode spelled `name` in `Code`.
+pub fn opcode(name: &str) -> Option<u8> {
+    NAMES
+        .iter()
+        .find(|(_, opcode)| *opcode == name)
+        .map(|(code, _)| *code)
+}
+
+/// The operand that follows an opcode. `Jump` is a uint counted from the end
+/// of the instruction, `Byte` a single raw byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    None,
+    Uint,
+    Jump,
+    Int,
+    Byte,
+    Float,
+    Char,
+    String,
+}
+
+pub fn operand_kind(code: u8) -> OperandKind {
+    match code {
+        Code::INTEGER => OperandKind::Int,
+        Code::BYTE => OperandKind::Byte,
+        Code::FLOAT => OperandKind::Float,
+        Code::CHAR => OperandKind::Char,
+        Code::STRING => OperandKind::String,
+        Code::BLOCK
+        | Code::FUNCTION
+        | Code::CLOSURE
+        | Code::JUMPIFFALSE
+        | Code::SHORTAND
+        | Code::SHORTOR => OperandKind::Jump,
+        Code::STOREID
+        | Code::ID
+        | Code::ALLOCATEREG
+        | Code::GROWREG
+        | Code::TAKEID
+        | Code::DIRECTCALL
+        | Code::NEWLIST
+        | Code::NEWMAP
+        | Code::STOREFASTID
+        | Code::REFID
+        | Code::CID
+        | Code::NATIVE
+        | Code::ALLOCATEGLOBAL
+        | Code::GLOBALID
+        | Code::STOREGLOBAL
+        | Code::GETBIND => OperandKind::Uint,
+        _ => OperandKind::None,
+    }
+}
+
+// Operands are encoded the same way on every platform so bytecode can be
+// saved on one machine and run on another. Indices, counts, lengths and jump
+// distances are unsigned LEB128 varints, Int constants are zigzag encoded
+// varints, Float constants are 8 little endian bytes and Chars are varints of
+// their scalar value.
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80)
+    }
+}
+
+fn read_varint(mut next: impl FnMut() -> Option<u8>) -> Option<u64> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = next()?;
+        let bits = (byte & 0x7f) as u64;
+        if shift >= u64::BITS || bits > u64::MAX >> shift {
+            return None;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+pub fn write_uint(output: &mut Vec<u8>, value: usize) {
+    write_varint(output, value as u64)
+}
+
+/// Overwrites `slot`, the bytes of a uint operand, with `value` padded to
+/// the same length so nothing after it moves. False if it does not fit.
+pub fn patch_uint(slot: &mut [u8], value: usize) -> bool {
+    let mut bytes = vec![];
+    write_uint(&mut bytes, value);
+    if bytes.len() > slot.len() {
+        return false;
+    }
+    bytes.resize(slot.len(), 0);
+    let last = slot.len() - 1;
+    for (index, byte) in bytes.iter().enumerate() {
+        slot[index] = match index == last {
+            true => byte & 0x7f,
+            false => byte | 0x80,
+        }
+    }
+    true
+}
+
+pub fn write_int(output: &mut Vec<u8>, value: i64) {
+    write_varint(output, ((value << 1) ^ (value >> 63)) as u64)
+}
+
+pub fn write_float(output: &mut Vec<u8>, value: f64) {
+    output.extend_from_slice(&value.to_le_bytes())
+}
+
+pub fn write_char(output: &mut Vec<u8>, value: char) {
+    write_varint(output, value as u64)
+}
+
+/// Reads an unsigned operand from `next`, returns None if the bytes run out
+/// or the value does not fit in a usize on this platform.
+pub fn read_uint(next: impl FnMut() -> Option<u8>) -> Option<usize> {
+    usize::try_from(read_varint(next)?).ok()
+}
+
+pub fn read_int(next: impl FnMut() -> Option<u8>) -> Option<i64> {
+    let zigzag = read_varint(next)?;
+    Some((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+}
+
+pub fn read_float(mut next: impl FnMut() -> Option<u8>) -> Option<f64> {
+    let mut bytes = [0; 8];
+    for byte in bytes.iter_mut() {
+        *byte = next()?;
+    }
+    Some(f64::from_le_bytes(bytes))
+}
+
+pub fn read_char(next: impl FnMut() -> Option<u8>) -> Option<char> {
+    char::from_u32(u32::try_from(read_varint(next)?).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(input: &[u8]) -> impl FnMut() -> Option<u8> + '_ {
+        let mut input = input.iter().copied();
+        move || input.next()
+    }
+
+    #[test]
+    fn uint_round_trip() {
+        let cases = [
+            (0, 1),
+            (127, 1),
+            (128, 2),
+            (16383, 2),
+            (16384, 3),
+            (u64::MAX as usize, 10),
+        ];
+        for (value, length) in cases {
+            let mut output = vec![];
+            write_uint(&mut output, value);
+            assert_eq!(output.len(), length, "length of {}", value);
+            assert_eq!(read_uint(bytes(&output)), Some(value));
+        }
+    }
+
+    #[test]
+    fn patched_uint_keeps_its_length() {
+        let mut output = vec![];
+        write_uint(&mut output, 300);
+        assert!(patch_uint(&mut output, 5));
+        assert_eq!(output.len(), 2);
+        assert_eq!(read_uint(bytes(&output)), Some(5));
+        assert!(!patch_uint(&mut output, 16384));
+    }
+
+    #[test]
+    fn int_round_trip() {
+        for value in [0, 1, -1, 63, -64, 64, -65, -16384, i64::MAX, i64::MIN] {
+            let mut output = vec![];
+            write_int(&mut output, value);
+            assert_eq!(read_int(bytes(&output)), Some(value));
+        }
+    }
+
+    #[test]
+    fn truncated_operands() {
+        let mut output = vec![];
+        write_uint(&mut output, 16384);
+        output.pop();
+        assert_eq!(read_uint(bytes(&output)), None);
+        assert_eq!(read_int(bytes(&[])), None);
+        // eleven bytes of continuation do not fit in 64 bits
+        assert_eq!(read_uint(bytes(&[0xff; 11])), None);
+    }
 }
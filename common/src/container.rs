@@ -0,0 +1,260 @@
+use crate::{
+    debug::{ChunkKind, DebugInfo, Symbol, SymbolKind},
+    error::NovaError,
+};
+
+/// First bytes of every `.nvc` file.
+pub const MAGIC: [u8; 4] = *b"NOVA";
+/// Bumped whenever the layout of the container or the bytecode changes.
+pub const VERSION: u16 = 4;
+
+/// A compiled program as stored in a `.nvc` file. Native functions are
+/// referenced by index in the code and by name here, so the loader can map
+/// them onto whatever order the host registered them in.
+///
+/// Layout, all integers little endian:
+/// magic, version u16, natives, files, lines, chunks, scopes, code length
+/// u64, code.
+/// Lists are a u32 count followed by their entries, strings a u32 length
+/// followed by utf-8 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    pub natives: Vec<String>,
+    pub debug: DebugInfo,
+    pub code: Vec<u8>,
+}
+
+pub fn new(natives: Vec<String>, debug: DebugInfo, code: Vec<u8>) -> Container {
+    Container {
+        natives,
+        debug,
+        code,
+    }
+}
+
+fn invalid(msg: &str) -> NovaError {
+    crate::error::file_error(format!("Invalid bytecode file: {}", msg))
+}
+
+fn write_len(output: &mut Vec<u8>, len: usize) {
+    output.extend_from_slice(&(len as u32).to_le_bytes())
+}
+
+fn write_string(output: &mut Vec<u8>, string: &str) {
+    write_len(output, string.len());
+    output.extend_from_slice(string.as_bytes())
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], NovaError> {
+        let end = self.position.checked_add(len);
+        match end.and_then(|end| self.input.get(self.position..end)) {
+            Some(bytes) => {
+                self.position += len;
+                Ok(bytes)
+            }
+            None => Err(invalid("file ends early")),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, NovaError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, NovaError> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.bytes(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn len(&mut self) -> Result<usize, NovaError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn u64(&mut self) -> Result<usize, NovaError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        usize::try_from(u64::from_le_bytes(bytes)).map_err(|_| invalid("offset out of range"))
+    }
+
+    fn string(&mut self) -> Result<String, NovaError> {
+        let len = self.len()?;
+        match String::from_utf8(self.bytes(len)?.to_vec()) {
+            Ok(string) => Ok(string),
+            Err(_) => Err(invalid("name is not valid utf-8")),
+        }
+    }
+}
+
+impl Container {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut output = vec![];
+        output.extend_from_slice(&MAGIC);
+        output.extend_from_slice(&VERSION.to_le_bytes());
+
+        write_len(&mut output, self.natives.len());
+        for name in self.natives.iter() {
+            write_string(&mut output, name)
+        }
+
+        write_len(&mut output, self.debug.files.len());
+        for file in self.debug.files.iter() {
+            write_string(&mut output, file)
+        }
+
+        write_len(&mut output, self.debug.lines.len());
+        for entry in self.debug.lines.iter() {
+            output.extend_from_slice(&(entry.offset as u64).to_le_bytes());
+            output.extend_from_slice(&(entry.line as u64).to_le_bytes());
+            write_len(&mut output, entry.file);
+        }
+
+        write_len(&mut output, self.debug.chunks.len());
+        for chunk in self.debug.chunks.iter() {
+            output.push(match chunk.kind {
+                ChunkKind::Block => 0,
+                ChunkKind::Function => 1,
+                ChunkKind::Closure => 2,
+            });
+            match &chunk.name {
+                Some(name) => {
+                    output.push(1);
+                    write_string(&mut output, name)
+                }
+                None => output.push(0),
+            }
+            output.extend_from_slice(&(chunk.start as u64).to_le_bytes());
+            output.extend_from_slice(&(chunk.end as u64).to_le_bytes());
+        }
+
+        write_len(&mut output, self.debug.scopes.len());
+        for scope in self.debug.scopes.iter() {
+            output.extend_from_slice(&(scope.start as u64).to_le_bytes());
+            output.extend_from_slice(&(scope.end as u64).to_le_bytes());
+            write_len(&mut output, scope.symbols.len());
+            for symbol in scope.symbols.iter() {
+                output.push(match symbol.kind {
+                    SymbolKind::Register => 0,
+                    SymbolKind::Global => 1,
+                    SymbolKind::Upvalue => 2,
+                    SymbolKind::Binding => 3,
+                });
+                output.extend_from_slice(&(symbol.index as u64).to_le_bytes());
+                write_string(&mut output, &symbol.name);
+                output.extend_from_slice(&(symbol.line as u64).to_le_bytes());
+                write_string(&mut output, &symbol.file);
+            }
+        }
+
+        output.extend_from_slice(&(self.code.len() as u64).to_le_bytes());
+        output.extend_from_slice(&self.code);
+        output
+    }
+
+    /// Checks the header and reads a container back from its bytes. The code
+    /// is taken as it is, the VM reports malformed instructions when it
+    /// reaches them.
+    pub fn decode(input: &[u8]) -> Result<Container, NovaError> {
+        let mut reader = Reader { input, position: 0 };
+        if !input.starts_with(&MAGIC) {
+            return Err(invalid("missing NOVA header"));
+        }
+        reader.bytes(MAGIC.len())?;
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(crate::error::file_error(format!(
+                "Bytecode file has format version {}, this build of nova reads version {}",
+                version, VERSION
+            )));
+        }
+
+        let mut natives = vec![];
+        for _ in 0..reader.len()? {
+            natives.push(reader.string()?)
+        }
+
+        let mut debug = crate::debug::new();
+        let mut files = vec![];
+        for _ in 0..reader.len()? {
+            let file = reader.string()?;
+            debug.files.insert(file.clone());
+            files.push(file)
+        }
+
+        for _ in 0..reader.len()? {
+            let offset = reader.u64()?;
+            let line = reader.u64()?;
+            match files.get(reader.len()?) {
+                Some(file) => debug.line(offset, line, file),
+                None => return Err(invalid("line entry names an unknown file")),
+            }
+        }
+
+        for _ in 0..reader.len()? {
+            let kind = match reader.u8()? {
+                0 => ChunkKind::Block,
+                1 => ChunkKind::Function,
+                2 => ChunkKind::Closure,
+                _ => return Err(invalid("unknown chunk kind")),
+            };
+            let name = match reader.u8()? {
+                0 => None,
+                _ => Some(reader.string()?),
+            };
+            let start = reader.u64()?;
+            let end = reader.u64()?;
+            debug.chunk(kind, name, start, end)
+        }
+
+        for _ in 0..reader.len()? {
+            let start = reader.u64()?;
+            let end = reader.u64()?;
+            let mut symbols = vec![];
+            for _ in 0..reader.len()? {
+                let kind = match reader.u8()? {
+                    0 => SymbolKind::Register,
+                    1 => SymbolKind::Global,
+                    2 => SymbolKind::Upvalue,
+                    3 => SymbolKind::Binding,
+                    _ => return Err(invalid("unknown symbol kind")),
+                };
+                symbols.push(Symbol {
+                    kind,
+                    index: reader.u64()?,
+                    name: reader.string()?,
+                    line: reader.u64()?,
+                    file: reader.string()?,
+                })
+            }
+            debug.scope(start, end, symbols)
+        }
+
+        let len = reader.u64()?;
+        let code = reader.bytes(len)?.to_vec();
+        if reader.position != input.len() {
+            return Err(invalid("unexpected bytes after the code"));
+        }
+
+        Ok(new(natives, debug, code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_code_length_is_an_error() {
+        let mut output = new(vec![], crate::debug::new(), vec![]).encode();
+        let start = output.len() - 8;
+        output[start..].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Container::decode(&output).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::table::Table;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkKind {
+    Block,
+    Function,
+    Closure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEntry {
+    pub offset: usize,
+    pub line: usize,
+    pub file: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkEntry {
+    pub kind: ChunkKind,
+    pub name: Option<String>,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ChunkEntry {
+    pub fn display_name(&self) -> String {
+        match (&self.name, self.kind) {
+            (Some(name), _) => name.clone(),
+            (None, ChunkKind::Block) => "<block>".to_string(),
+            (None, ChunkKind::Function) => "<function>".to_string(),
+            (None, ChunkKind::Closure) => "<closure>".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Register,
+    Global,
+    Upvalue,
+    Binding,
+}
+
+/// A name the compiler resolved to an index, with where it was declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub index: usize,
+    pub name: String,
+    pub line: usize,
+    pub file: String,
+}
+
+/// Names declared in one function or closure body, one let binding, or at
+/// the top level, whose scope covers the whole program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    pub start: usize,
+    pub end: usize,
+    pub symbols: Vec<Symbol>,
+}
+
+/// Maps bytecode offsets back to source lines and to the block, function or
+/// closure body they were compiled from. Offsets are relative to the chunk
+/// being compiled until the chunk is appended into its parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugInfo {
+    pub files: Table<String>,
+    pub lines: Vec<LineEntry>,
+    pub chunks: Vec<ChunkEntry>,
+    pub scopes: Vec<Scope>,
+}
+
+pub fn new() -> DebugInfo {
+    DebugInfo {
+        files: crate::table::new(),
+        lines: vec![],
+        chunks: vec![],
+        scopes: vec![],
+    }
+}
+
+impl Default for DebugInfo {
+    fn default() -> Self {
+        new()
+    }
+}
+
+impl DebugInfo {
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.chunks.is_empty() && self.scopes.is_empty()
+    }
+
+    pub fn line(&mut self, offset: usize, line: usize, file: &str) {
+        self.files.insert(file.to_string());
+        let file = self.files.get_index(file.to_string()).unwrap_or_default();
+        if let Some(last) = self.lines.last_mut() {
+            if last.offset == offset {
+                last.line = line;
+                last.file = file;
+                return;
+            }
+            if last.line == line && last.file == file {
+                return;
+            }
+        }
+        self.lines.push(LineEntry { offset, line, file })
+    }
+
+    pub fn chunk(&mut self, kind: ChunkKind, name: Option<String>, start: usize, end: usize) {
+        self.chunks.push(ChunkEntry {
+            kind,
+            name,
+            start,
+            end,
+        })
+    }
+
+    pub fn scope(&mut self, start: usize, end: usize, symbols: Vec<Symbol>) {
+        self.scopes.push(Scope {
+            start,
+            end,
+            symbols,
+        })
+    }
+
+    /// Moves every offset forward, used when bytes are prepended to a chunk.
+    pub fn shift(&mut self, by: usize) {
+        for entry in self.lines.iter_mut() {
+            entry.offset += by;
+        }
+        for entry in self.chunks.iter_mut() {
+            entry.start += by;
+            entry.end += by;
+        }
+        for scope in self.scopes.iter_mut() {
+            scope.start += by;
+            scope.end = scope.end.saturating_add(by);
+        }
+    }
+
+    /// Merges the debug info of a nested chunk placed at `at` in this chunk.
+    pub fn append(&mut self, mut other: DebugInfo, at: usize) {
+        other.shift(at);
+        for entry in other.lines {
+            if let Some(file) = other.files.retreive(entry.file).cloned() {
+                self.line(entry.offset, entry.line, &file)
+            }
+        }
+        self.chunks.extend(other.chunks);
+        self.scopes.extend(other.scopes);
+    }
+
+    pub fn line_at(&self, offset: usize) -> Option<(usize, &str)> {
+        let index = self.lines.partition_point(|entry| entry.offset <= offset);
+        let entry = self.lines.get(index.checked_sub(1)?)?;
+        let file = self.files.retreive(entry.file)?;
+        Some((entry.line, file))
+    }
+
+    /// Name of a body for backtraces and profiles, unnamed bodies are told
+    /// apart by the line they start on, as in `<block>@12`.
+    pub fn label(&self, chunk: &ChunkEntry) -> String {
+        match (&chunk.name, self.line_at(chunk.start)) {
+            (None, Some((line, _))) => format!("{}@{}", chunk.display_name(), line),
+            _ => chunk.display_name(),
+        }
+    }
+
+    /// Returns the innermost body of any kind containing `offset`.
+    pub fn chunk_at(&self, offset: usize) -> Option<&ChunkEntry> {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.start <= offset && offset < chunk.end)
+            .min_by_key(|chunk| chunk.end - chunk.start)
+    }
+
+    /// Returns the innermost function or closure body containing `offset`,
+    /// blocks are only considered when they were bound to a name.
+    pub fn function_at(&self, offset: usize) -> Option<&ChunkEntry> {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.start <= offset && offset < chunk.end)
+            .filter(|chunk| chunk.kind != ChunkKind::Block || chunk.name.is_some())
+            .min_by_key(|chunk| chunk.end - chunk.start)
+    }
+
+    /// The symbol for `index` as seen by the code at `offset`, looked up in
+    /// the innermost scope around it first. Globals are found from anywhere.
+    pub fn symbol(&self, kind: SymbolKind, index: usize, offset: usize) -> Option<&Symbol> {
+        let mut scopes: Vec<&Scope> = self
+            .scopes
+            .iter()
+            .filter(|scope| {
+                kind == SymbolKind::Global || (scope.start <= offset && offset < scope.end)
+            })
+            .collect();
+        scopes.sort_by_key(|scope| scope.end - scope.start);
+        scopes
+            .into_iter()
+            .flat_map(|scope| scope.symbols.iter())
+            .find(|symbol| symbol.kind == kind && symbol.index == index)
+    }
+}
@@ -15,7 +15,7 @@ fn print_line(line: usize, row: Option<usize>, file: &str, msg: &str) {
             if linenumber == line {
                 let spaces = line.to_string().chars().count();
                 if let Some(mut row) = row {
-                    row = (row + 1) - spaces;
+                    row = (row + 1).saturating_sub(spaces);
                     if let Ok(ip) = l {
                         for _ in 0..spaces {
                             print!(" ")
@@ -54,13 +54,26 @@ fn print_line(line: usize, row: Option<usize>, file: &str, msg: &str) {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ErrorType {
     File,
     Lexing,
     Parsing,
     Compiler,
     Runtime,
+    /// The VM used up the instructions it was allowed to run.
+    OutOfFuel,
+    /// The VM ran past its deadline.
+    Timeout,
+    /// The VM was stopped through its interrupt flag.
+    Interrupted,
+}
+
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    pub function: String,
+    pub file: String,
+    pub line: usize,
 }
 
 #[derive(Debug)]
@@ -71,6 +84,7 @@ pub struct NovaError {
     line: usize,
     row: usize,
     filepath: String,
+    backtrace: Vec<TraceFrame>,
 }
 
 impl NovaError {
@@ -85,9 +99,41 @@ impl NovaError {
                 print_line(self.line, Some(self.row), &self.filepath, &self.msg);
                 println!("Note: {}", self.note);
             }
-            ErrorType::Parsing => todo!(),
-            ErrorType::Runtime => {
-                println!("Runtime Error: {}", self.msg)
+            ErrorType::Parsing => {
+                println!("Parsing Error in: {}", self.filepath);
+                print_line(self.line, Some(self.row), &self.filepath, &self.msg);
+                println!("Note: {}", self.note);
+            }
+            ErrorType::Runtime
+            | ErrorType::OutOfFuel
+            | ErrorType::Timeout
+            | ErrorType::Interrupted => {
+                let title = match self.error {
+                    ErrorType::OutOfFuel => "Out of Fuel",
+                    ErrorType::Timeout => "Timeout",
+                    ErrorType::Interrupted => "Interrupted",
+                    _ => "Runtime Error",
+                };
+                println!("{}: {}", title, self.msg);
+                if !self.backtrace.is_empty() {
+                    print_line(self.line, None, &self.filepath, &self.msg);
+                    println!("Backtrace:");
+                    // deep recursion is shown by its innermost and outermost frames
+                    let hidden = self.backtrace.len().saturating_sub(20);
+                    for (index, frame) in self.backtrace.iter().enumerate() {
+                        if hidden > 0 && (10..10 + hidden).contains(&index) {
+                            if index == 10 {
+                                println!("  ... {} more frames", hidden)
+                            }
+                            continue;
+                        }
+                        if frame.file.is_empty() {
+                            println!("  at {} (line {})", frame.function, frame.line)
+                        } else {
+                            println!("  at {} ({}:{})", frame.function, frame.file, frame.line)
+                        }
+                    }
+                }
             }
             ErrorType::Compiler => {
                 println!("Compiler Error in: {}", self.filepath);
@@ -96,6 +142,38 @@ impl NovaError {
             }
         }
     }
+
+    /// Attaches a Nova level backtrace, innermost frame first. The source
+    /// location of the error is taken from the innermost frame.
+    pub fn with_backtrace(mut self, backtrace: Vec<TraceFrame>) -> NovaError {
+        if let Some(frame) = backtrace.first() {
+            self.line = frame.line;
+            self.filepath = frame.file.clone();
+        }
+        self.backtrace = backtrace;
+        self
+    }
+
+    pub fn backtrace(&self) -> &[TraceFrame] {
+        &self.backtrace
+    }
+
+    pub fn kind(&self) -> ErrorType {
+        self.error
+    }
+}
+
+// lets natives written against `vm::convert` fail with a plain message
+impl From<String> for NovaError {
+    fn from(msg: String) -> NovaError {
+        runetime_error(msg)
+    }
+}
+
+impl From<&str> for NovaError {
+    fn from(msg: &str) -> NovaError {
+        runetime_error(msg.to_string())
+    }
 }
 
 pub fn file_error(msg: String) -> NovaError {
@@ -106,6 +184,7 @@ pub fn file_error(msg: String) -> NovaError {
         line: 0,
         filepath: String::new(),
         row: 0,
+        backtrace: vec![],
     }
 }
 
@@ -123,6 +202,25 @@ pub fn lexer_error(
         line,
         row,
         filepath,
+        backtrace: vec![],
+    }
+}
+
+pub fn parser_error(
+    msg: String,
+    note: String,
+    line: usize,
+    row: usize,
+    filepath: String,
+) -> NovaError {
+    NovaError {
+        error: ErrorType::Parsing,
+        msg,
+        note,
+        line,
+        row,
+        filepath,
+        backtrace: vec![],
     }
 }
 
@@ -134,9 +232,35 @@ pub fn runetime_error(msg: String) -> NovaError {
         line: 0,
         filepath: String::new(),
         row: 0,
+        backtrace: vec![],
+    }
+}
+
+// errors for the VM limits, which stop a program between two instructions
+fn limit_error(error: ErrorType, msg: String) -> NovaError {
+    NovaError {
+        error,
+        msg,
+        note: String::new(),
+        line: 0,
+        filepath: String::new(),
+        row: 0,
+        backtrace: vec![],
     }
 }
 
+pub fn fuel_error(msg: String) -> NovaError {
+    limit_error(ErrorType::OutOfFuel, msg)
+}
+
+pub fn timeout_error(msg: String) -> NovaError {
+    limit_error(ErrorType::Timeout, msg)
+}
+
+pub fn interrupt_error(msg: String) -> NovaError {
+    limit_error(ErrorType::Interrupted, msg)
+}
+
 pub fn compiler_error(note: String, line: usize, filepath: String) -> NovaError {
     NovaError {
         error: ErrorType::Compiler,
@@ -145,5 +269,6 @@ pub fn compiler_error(note: String, line: usize, filepath: String) -> NovaError
         line: line + 1,
         filepath,
         row: 0,
+        backtrace: vec![],
     }
 }
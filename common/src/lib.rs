@@ -1,5 +1,8 @@
 pub mod code;
+pub mod container;
+pub mod debug;
 pub mod error;
+pub mod signature;
 pub mod table;
 pub mod tokens;
 pub mod utilities;
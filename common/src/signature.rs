@@ -0,0 +1,59 @@
+/// The kind of value a native takes as an argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Any,
+    Int,
+    Float,
+    Bool,
+    Char,
+    String,
+    List,
+    Map,
+    /// A Rust object handed to the program, see `vm::state::UserData`.
+    UserData,
+    /// A function, closure or block.
+    Callable,
+}
+
+impl Type {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::Any => "Any",
+            Type::Int => "Int",
+            Type::Float => "Float",
+            Type::Bool => "Bool",
+            Type::Char => "Char",
+            Type::String => "String",
+            Type::List => "List",
+            Type::Map => "Map",
+            Type::UserData => "UserData",
+            Type::Callable => "Callable",
+        }
+    }
+}
+
+/// The parameters of a native, in the order they are written in a call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+}
+
+pub fn new(params: &[Type]) -> Signature {
+    Signature {
+        params: params.to_vec(),
+    }
+}
+
+impl Signature {
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    /// The arity as errors word it, "1 argument" or "3 arguments".
+    pub fn arguments(&self) -> String {
+        match self.arity() {
+            1 => "1 argument".to_string(),
+            arity => format!("{} arguments", arity),
+        }
+    }
+}
@@ -32,6 +32,10 @@ impl<T: Eq> Table<T> {
     pub fn is_empty(&self) -> bool {
         self.items.is_empty()
     }
+    #[inline(always)]
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.items.iter()
+    }
 }
 
 impl<T: std::fmt::Debug> fmt::Debug for Table<T> {
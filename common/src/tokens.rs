@@ -13,8 +13,11 @@ pub enum Operator {
     Or,
     Not,
     Equals,
+    NotEquals,
     Gtr,
     Lss,
+    Gte,
+    Lte,
     Invert,
     Mod,
     Add,
@@ -32,6 +35,8 @@ pub enum Operator {
 pub enum Token {
     // Line information for Error messages
     LinePosition(usize),
+    // Line and row of the following symbol, consumed by the parser
+    Position(usize, usize),
 
     // Identifer types
     Reg(String),
@@ -58,13 +63,21 @@ pub enum Token {
     Doblock(TokenList),
     Function(TokenList, TokenList),
     Closure(TokenList, TokenList, TokenList),
-    List(TokenList),
+    // Literals carry the number of values written in them, which the parser
+    // fills in
+    List(TokenList, usize),
+    // Keys and values alternating, written as #[key value ...]
+    Map(TokenList, usize),
+    // Right hand side of && or ||, only evaluated when the left side does not
+    // decide the result
+    ShortCircuit(Operator, TokenList),
     Bindings(TokenList),
     LetBinding(TokenList, TokenList),
     Arguments(TokenList),
 
-    // Function call
-    Call(String),
+    // Function call, with the number of arguments written between its
+    // parentheses, which the parser fills in
+    Call(String, usize),
     CurrentFile(String),
     Op(Operator),
 
@@ -78,10 +91,15 @@ impl Token {
     pub fn precedence(&self) -> usize {
         match self {
             Token::Op(Operator::Assign) => 2,
-            Token::Op(Operator::And) => 6,
-            Token::Op(Operator::Or) => 7,
+            Token::Op(Operator::Or) => 6,
+            Token::Op(Operator::And) => 7,
             Token::Op(Operator::Not) => 8,
-            Token::Op(Operator::Equals) | Token::Op(Operator::Gtr) | Token::Op(Operator::Lss) => 9,
+            Token::Op(Operator::Equals)
+            | Token::Op(Operator::NotEquals)
+            | Token::Op(Operator::Gtr)
+            | Token::Op(Operator::Lss)
+            | Token::Op(Operator::Gte)
+            | Token::Op(Operator::Lte) => 9,
             Token::Op(Operator::Add) | Token::Op(Operator::Sub) => 12,
             Token::Op(Operator::Mul) | Token::Op(Operator::Div) | Token::Op(Operator::Mod) => 13,
             Token::Op(Operator::Invert) => 15,
@@ -94,7 +112,7 @@ impl Token {
             Token::Op(Operator::Invert) => false,
             Token::Op(Operator::Or) => true,
             Token::Op(Operator::And) => true,
-            Token::Op(Operator::Not) => true,
+            Token::Op(Operator::Not) => false,
             Token::Op(Operator::Assign) => false,
             Token::Op(Operator::Add) | Token::Op(Operator::Sub) => true,
             Token::Op(Operator::Mul) | Token::Op(Operator::Div) | Token::Op(Operator::Mod) => true,
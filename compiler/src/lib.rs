@@ -1,10 +1,42 @@
-use common::code::Code;
+use common::code::{self, Code};
+use common::debug::{ChunkKind, DebugInfo, Symbol, SymbolKind};
 use common::error::NovaError;
-use common::tokens::Token;
+use common::signature::Signature;
+use common::tokens::{Operator, Token};
+use std::collections::HashMap;
+
+// name of the variable or module a block, function or closure is assigned to
+fn bound_name(input: &[Token], index: usize) -> Option<String> {
+    match (
+        index.checked_sub(1).and_then(|before| input.get(before)),
+        input.get(index + 1),
+    ) {
+        (Some(Token::RegStore(id)), Some(Token::Op(Operator::Assign)))
+        | (Some(Token::GlobalReg(id)), Some(Token::Op(Operator::Assign))) => Some(id.clone()),
+        _ => None,
+    }
+}
+
+// variable the result of the call at `index` is assigned to, as in
+// `list = push(list item)`
+fn assigned_to(input: &[Token], index: usize) -> Option<&str> {
+    if input.get(index + 1) != Some(&Token::Op(Operator::Assign)) {
+        return None;
+    }
+    for token in input[..index].iter().rev() {
+        match token {
+            Token::RegStore(id) => return Some(id),
+            Token::Op(Operator::Assign) | Token::GlobalReg(_) | Token::RegRef(_) => return None,
+            _ => {}
+        }
+    }
+    None
+}
 
 pub fn new() -> Compiler {
     Compiler {
         native_functions: common::table::new(),
+        signatures: HashMap::new(),
         variables: common::table::new(),
         output: Vec::new(),
         currentline: 0,
@@ -13,6 +45,8 @@ pub fn new() -> Compiler {
         global: common::table::new(),
         entry: 0,
         bindings: common::table::new(),
+        debug: common::debug::new(),
+        symbols: vec![],
     }
 }
 
@@ -22,10 +56,16 @@ pub struct Compiler {
     pub variables: common::table::Table<String>,
     pub upvalues: common::table::Table<String>,
     pub native_functions: common::table::Table<String>,
+    /// Natives with a known signature, calls to them with more arguments
+    /// between their parentheses than they take are rejected. Fewer may be
+    /// passed, the rest can already be on the stack.
+    pub signatures: HashMap<String, Signature>,
     output: Vec<u8>,
     pub currentline: usize,
     filepath: String,
     pub entry: usize,
+    pub debug: DebugInfo,
+    symbols: Vec<Symbol>,
 }
 
 impl Compiler {
@@ -40,40 +80,130 @@ impl Compiler {
     #[inline(always)]
     pub fn compile(&mut self, input: Vec<Token>, filepath: String) -> Result<Vec<u8>, NovaError> {
         self.filepath = filepath;
-        let chunks = match self.compile_chunk(input) {
-            Ok(chunk) => chunk,
-            Err(error) => return Err(error),
-        };
+        self.debug = common::debug::new();
+        let chunks = self.compile_chunk(input)?;
 
         let packaged = self.load_package(chunks);
         let packaged = self.load_globals(packaged);
 
+        self.debug.shift(self.output.len());
+        let symbols = std::mem::take(&mut self.symbols);
+        self.debug.scope(0, usize::MAX, symbols);
         self.output.extend_from_slice(&packaged);
         Ok(self.output.to_owned())
     }
+
+    /// Compiles input to run on a VM that already ran everything compiled
+    /// before, as the REPL does. Returns only the new code, which allocates
+    /// just the registers and globals introduced by this input. On error the
+    /// compiler is left as it was before the call.
+    pub fn compile_increment(
+        &mut self,
+        input: Vec<Token>,
+        filepath: String,
+    ) -> Result<Vec<u8>, NovaError> {
+        let variables = self.variables.clone();
+        let global = self.global.clone();
+        self.filepath = filepath;
+        self.debug = common::debug::new();
+        let chunk = match self.compile_chunk(input) {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                self.variables = variables;
+                self.global = global;
+                self.symbols.clear();
+                return Err(error);
+            }
+        };
+
+        let mut package = vec![];
+        if self.output.is_empty() {
+            package.push(Code::ALLOCATEREG);
+        } else {
+            package.push(Code::GROWREG);
+        }
+        code::write_uint(&mut package, self.variables.len() - variables.len());
+        package.push(Code::ALLOCATEGLOBAL);
+        code::write_uint(&mut package, self.global.len() - global.len());
+        package.extend_from_slice(&chunk);
+
+        self.debug
+            .shift(self.output.len() + package.len() - chunk.len());
+        let symbols = std::mem::take(&mut self.symbols);
+        self.debug.scope(0, usize::MAX, symbols);
+        self.output.extend_from_slice(&package);
+        Ok(package)
+    }
+
+    // compiles a chunk that will be placed inside the current one, keeping its
+    // debug info relative to its own start
+    fn compile_nested(&mut self, input: Vec<Token>) -> Result<(Vec<u8>, DebugInfo), NovaError> {
+        let outer = std::mem::take(&mut self.debug);
+        let bytes = self.compile_chunk(input);
+        let inner = std::mem::replace(&mut self.debug, outer);
+        Ok((bytes?, inner))
+    }
+
+    fn declare(&mut self, kind: SymbolKind, index: usize, name: &str) {
+        self.symbols.push(Symbol {
+            kind,
+            index,
+            name: name.to_string(),
+            line: self.currentline + 1,
+            file: self.filepath.clone(),
+        })
+    }
+
+    // only too many arguments are an error, fewer stays legal since a call
+    // takes the rest from values already on the stack, as in
+    // `list remove(index)` or when an earlier line left them there
+    fn check_arity(&self, name: &str, count: usize) -> Result<(), NovaError> {
+        match self.signatures.get(name) {
+            Some(signature) if count > signature.arity() => Err(common::error::compiler_error(
+                format!(
+                    "[CALL] {} takes {}, found {}",
+                    name,
+                    signature.arguments(),
+                    count
+                ),
+                self.currentline,
+                self.filepath.clone(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    #[inline(always)]
+    fn mark_line(&mut self, offset: usize) {
+        self.debug
+            .line(offset, self.currentline + 1, &self.filepath.clone())
+    }
+
     #[inline(always)]
     fn compile_chunk(&mut self, input: Vec<Token>) -> Result<Vec<u8>, NovaError> {
         let mut output = vec![];
-        for op in input.iter() {
+        self.mark_line(0);
+        for (position, op) in input.iter().enumerate() {
             match op {
-                Token::LinePosition(line) => self.currentline = *line,
+                Token::LinePosition(line) => {
+                    self.currentline = *line;
+                    self.mark_line(output.len());
+                }
+                Token::Position(_, _) => {}
                 Token::Reg(id) => match id.as_str() {
                     "true" => output.push(Code::TRUE),
                     "false" => output.push(Code::FALSE),
                     _ => {
                         if let Some(index) = self.variables.get_index(id.to_string()) {
                             output.push(Code::ID);
-                            let bytes = (index as u16).to_ne_bytes();
-                            output.extend_from_slice(&bytes);
+                            code::write_uint(&mut output, index);
                         } else if let Some(index) = self.upvalues.get_index(id.to_string()) {
                             output.push(Code::CID);
-                            let bytes = index.to_ne_bytes();
-                            output.extend_from_slice(&bytes);
+                            code::write_uint(&mut output, index);
                         } else {
                             if let Some(index) = self.global.get_index(id.to_string()) {
                                 output.push(Code::GLOBALID);
-                                let bytes = index.to_ne_bytes();
-                                output.extend_from_slice(&bytes);
+                                code::write_uint(&mut output, index);
                                 continue;
                             }
 
@@ -88,14 +218,13 @@ impl Compiler {
                 Token::RegStore(id) => {
                     if let Some(index) = self.variables.get_index(id.to_string()) {
                         output.push(Code::STOREID);
-                        let bytes = index.to_ne_bytes();
-                        output.extend_from_slice(&bytes);
+                        code::write_uint(&mut output, index);
                     } else {
                         self.variables.insert(id.to_string());
                         let index = self.variables.len() - 1;
+                        self.declare(SymbolKind::Register, index, id);
                         output.push(Code::STOREID);
-                        let bytes = index.to_ne_bytes();
-                        output.extend_from_slice(&bytes);
+                        code::write_uint(&mut output, index);
                     }
                 }
                 Token::RegStoreFast(id) => {
@@ -104,9 +233,9 @@ impl Compiler {
                     } else {
                         self.variables.insert(id.to_string());
                         let index = self.variables.len() - 1;
+                        self.declare(SymbolKind::Register, index, id);
                         output.push(Code::STOREFASTID);
-                        let bytes = index.to_ne_bytes();
-                        output.extend_from_slice(&bytes);
+                        code::write_uint(&mut output, index);
                     }
                 }
                 Token::StoreFastBindId(id) => {
@@ -114,14 +243,14 @@ impl Compiler {
                         todo!()
                     } else {
                         self.bindings.insert(id.to_string());
+                        self.declare(SymbolKind::Binding, self.bindings.len() - 1, id);
                         output.push(Code::STOREBIND);
                     }
                 }
                 Token::BindingRef(id) => {
                     if let Some(index) = self.bindings.get_index(id.to_string()) {
                         output.push(Code::GETBIND);
-                        let bytes = index.to_ne_bytes();
-                        output.extend_from_slice(&bytes);
+                        code::write_uint(&mut output, index);
                     } else {
                         return Err(common::error::compiler_error(
                             format!("{} is not initialized", &id),
@@ -137,47 +266,48 @@ impl Compiler {
                         output.push(int);
                     } else {
                         output.push(Code::INTEGER);
-                        let int = value.to_ne_bytes();
-                        output.extend_from_slice(&int);
+                        code::write_int(&mut output, *value);
                     }
                 }
                 Token::Float(value) => {
                     output.push(Code::FLOAT);
-                    let float = value.to_ne_bytes();
-                    output.extend_from_slice(&float);
+                    code::write_float(&mut output, *value);
                 }
                 Token::String(string) => {
                     output.push(Code::STRING);
-                    let size = string.len().to_ne_bytes();
-                    output.extend_from_slice(&size);
+                    code::write_uint(&mut output, string.len());
                     let cast = string.as_bytes();
                     output.extend_from_slice(cast);
                 }
                 Token::Char(c) => {
                     output.push(Code::CHAR);
-                    output.push(*c as u8);
+                    code::write_char(&mut output, *c);
                 }
                 Token::Symbol(_) => todo!(),
                 Token::Bool(_) => todo!(),
                 Token::BlockLiteral(block) => {
-                    let mut bytes = match self.compile_chunk(block.to_vec()) {
-                        Ok(chunk) => chunk,
-                        Err(error) => return Err(error),
-                    };
+                    let (mut bytes, mut debug) = self.compile_nested(block.to_vec())?;
+                    debug.chunk(
+                        ChunkKind::Block,
+                        bound_name(&input, position),
+                        0,
+                        bytes.len(),
+                    );
                     output.push(Code::BLOCK);
-                    let cast = bytes.len();
-                    let int = cast.to_ne_bytes();
-                    output.extend_from_slice(&int);
-                    output.append(&mut bytes)
+                    code::write_uint(&mut output, bytes.len());
+                    self.debug.append(debug, output.len());
+                    output.append(&mut bytes);
+                    self.mark_line(output.len());
                 }
-                Token::Function(input, logic) => {
+                Token::Function(parameters, logic) => {
                     let mut function_c = new();
                     function_c.currentline = self.currentline;
                     function_c.native_functions = self.native_functions.clone();
+                    function_c.signatures = self.signatures.clone();
                     function_c.global = self.global.clone();
                     function_c.filepath = self.filepath.clone();
                     let mut arguments = vec![];
-                    for args in input.iter().rev() {
+                    for args in parameters.iter().rev() {
                         match args {
                             Token::Reg(id) => arguments.push(Token::RegStoreFast(id.to_string())),
                             _ => {
@@ -186,21 +316,27 @@ impl Compiler {
                         }
                     }
                     arguments.extend_from_slice(logic);
-                    let bytes = match function_c.compile_chunk(arguments.to_vec()) {
-                        Ok(chunk) => chunk,
-                        Err(error) => return Err(error),
-                    };
+                    let bytes = function_c.compile_chunk(arguments.to_vec())?;
 
                     let mut bytes = function_c.load_package(bytes);
+                    function_c.debug.chunk(
+                        ChunkKind::Function,
+                        bound_name(&input, position),
+                        0,
+                        bytes.len(),
+                    );
+                    let symbols = std::mem::take(&mut function_c.symbols);
+                    function_c.debug.scope(0, bytes.len(), symbols);
 
                     output.push(Code::FUNCTION);
-                    let cast = bytes.len();
-                    let int = cast.to_ne_bytes();
-                    output.extend_from_slice(&int);
+                    code::write_uint(&mut output, bytes.len());
+                    self.debug.append(function_c.debug, output.len());
                     output.append(&mut bytes);
                     self.global = function_c.global.clone();
+                    self.currentline = function_c.currentline;
+                    self.mark_line(output.len());
                 }
-                Token::Call(name) => match name.as_str() {
+                Token::Call(name, count) => match name.as_str() {
                     "loop" => output.push(Code::LOOP),
                     "range" => output.push(Code::RANGE),
                     "for" => {
@@ -213,26 +349,31 @@ impl Compiler {
                     "rec" => output.push(Code::REC),
                     _ => {
                         if let Some(index) = self.native_functions.get_index(name.to_string()) {
+                            self.check_arity(name, *count)?;
+                            // the variable is overwritten by the result anyway, so the
+                            // VM may hand its value to the native to update in place
+                            if let Some(index) = assigned_to(&input, position)
+                                .and_then(|id| self.variables.get_index(id.to_string()))
+                            {
+                                output.push(Code::TAKEID);
+                                code::write_uint(&mut output, index);
+                            }
                             output.push(Code::NATIVE);
-                            let bytes = index.to_ne_bytes();
-                            output.extend_from_slice(&bytes);
+                            code::write_uint(&mut output, index);
                             continue;
                         }
 
                         if let Some(index) = self.variables.get_index(name.to_string()) {
                             output.push(Code::DIRECTCALL);
-                            let bytes = index.to_ne_bytes();
-                            output.extend_from_slice(&bytes);
+                            code::write_uint(&mut output, index);
                         } else if let Some(index) = self.upvalues.get_index(name.to_string()) {
                             output.push(Code::CID);
-                            let bytes = index.to_ne_bytes();
-                            output.extend_from_slice(&bytes);
+                            code::write_uint(&mut output, index);
                             output.push(Code::CALL)
                         } else {
                             if let Some(index) = self.global.get_index(name.to_string()) {
                                 output.push(Code::GLOBALID);
-                                let bytes = index.to_ne_bytes();
-                                output.extend_from_slice(&bytes);
+                                code::write_uint(&mut output, index);
                                 output.push(Code::CALL);
                                 continue;
                             }
@@ -253,13 +394,16 @@ impl Compiler {
                     common::tokens::Operator::ModuleCall => todo!(),
                     common::tokens::Operator::UserFunctionChain => todo!(),
                     common::tokens::Operator::StoreTemp => todo!(),
-                    common::tokens::Operator::And => todo!(),
-                    common::tokens::Operator::Or => todo!(),
-                    common::tokens::Operator::Not => todo!(),
+                    common::tokens::Operator::And => output.push(Code::AND),
+                    common::tokens::Operator::Or => output.push(Code::OR),
+                    common::tokens::Operator::Not => output.push(Code::NOT),
                     common::tokens::Operator::Equals => output.push(Code::EQUALS),
+                    common::tokens::Operator::NotEquals => output.push(Code::NOTEQUALS),
                     common::tokens::Operator::Gtr => output.push(Code::GTR),
                     common::tokens::Operator::Lss => output.push(Code::LSS),
-                    common::tokens::Operator::Invert => todo!(),
+                    common::tokens::Operator::Gte => output.push(Code::GTE),
+                    common::tokens::Operator::Lte => output.push(Code::LTE),
+                    common::tokens::Operator::Invert => output.push(Code::NOT),
                     common::tokens::Operator::Mod => output.push(Code::MODULO),
                     common::tokens::Operator::Add => output.push(Code::ADD),
                     common::tokens::Operator::Sub => output.push(Code::SUB),
@@ -271,38 +415,65 @@ impl Compiler {
                     common::tokens::Operator::Continue => todo!(),
                     common::tokens::Operator::ResolveBind => todo!(),
                 },
-                Token::List(list) => {
-                    let mut bytes = match self.compile_chunk(list.to_vec()) {
-                        Ok(chunk) => chunk,
-                        Err(error) => return Err(error),
-                    };
+                Token::List(list, count) => {
+                    let (mut bytes, debug) = self.compile_nested(list.to_vec())?;
                     // removeing last ret statement
                     bytes.pop();
+                    self.debug.append(debug, output.len());
                     output.extend_from_slice(&bytes);
                     output.push(Code::NEWLIST);
-                    let cast = (list.len()) as u64;
-                    let int = cast.to_ne_bytes();
-                    output.extend_from_slice(&int);
+                    code::write_uint(&mut output, *count);
+                }
+                Token::Map(map, count) => {
+                    if count % 2 != 0 {
+                        return Err(common::error::compiler_error(
+                            "Map literal has a key without a value".to_string(),
+                            self.currentline,
+                            self.filepath.clone(),
+                        ));
+                    }
+                    let (mut bytes, debug) = self.compile_nested(map.to_vec())?;
+                    // removeing last ret statement
+                    bytes.pop();
+                    self.debug.append(debug, output.len());
+                    output.extend_from_slice(&bytes);
+                    output.push(Code::NEWMAP);
+                    code::write_uint(&mut output, count / 2);
+                }
+                Token::ShortCircuit(operator, right) => {
+                    let (mut bytes, debug) = self.compile_nested(right.to_vec())?;
+                    // removeing last ret statement
+                    bytes.pop();
+                    match operator {
+                        Operator::And => {
+                            bytes.push(Code::AND);
+                            output.push(Code::SHORTAND)
+                        }
+                        _ => {
+                            bytes.push(Code::OR);
+                            output.push(Code::SHORTOR)
+                        }
+                    }
+                    code::write_uint(&mut output, bytes.len());
+                    self.debug.append(debug, output.len());
+                    output.append(&mut bytes);
+                    self.mark_line(output.len());
                 }
                 Token::Arguments(_) => todo!(),
                 Token::ConditionalBlock(block) => {
-                    let mut bytes = match self.compile_chunk(block.to_vec()) {
-                        Ok(chunk) => chunk,
-                        Err(error) => return Err(error),
-                    };
+                    let (mut bytes, debug) = self.compile_nested(block.to_vec())?;
                     bytes.pop();
 
                     output.push(Code::JUMPIFFALSE);
-                    let cast = bytes.len();
-                    let int = (cast as u32).to_ne_bytes();
-                    output.extend_from_slice(&int);
-                    output.append(&mut bytes)
+                    code::write_uint(&mut output, bytes.len());
+                    self.debug.append(debug, output.len());
+                    output.append(&mut bytes);
+                    self.mark_line(output.len());
                 }
                 Token::RegRef(id) => {
                     if let Some(index) = self.variables.get_index(id.to_string()) {
                         output.push(Code::REFID);
-                        let bytes = (index as u16).to_ne_bytes();
-                        output.extend_from_slice(&bytes);
+                        code::write_uint(&mut output, index);
                     } else {
                         return Err(common::error::compiler_error(
                             format!("{} is not initialized", &id),
@@ -311,7 +482,7 @@ impl Compiler {
                         ));
                     }
                 }
-                Token::Closure(closed, input, logic) => {
+                Token::Closure(closed, parameters, logic) => {
                     // collect upvalues into list
                     let mut upvalues = common::table::new();
 
@@ -336,27 +507,27 @@ impl Compiler {
                     }
 
                     // todo setup input list
-                    let mut bytes = match self.compile_chunk(closed.to_vec()) {
-                        Ok(chunk) => chunk,
-                        Err(error) => return Err(error),
-                    };
+                    let (mut bytes, debug) = self.compile_nested(closed.to_vec())?;
                     // removeing last ret statement
                     bytes.pop();
+                    self.debug.append(debug, output.len());
                     output.extend_from_slice(&bytes);
                     // newarray
                     output.push(Code::NEWLIST);
-                    let cast = (closed.len()) as u64;
-                    let int = cast.to_ne_bytes();
-                    output.extend_from_slice(&int);
+                    code::write_uint(&mut output, closed.len());
 
                     let mut function_c = new();
+                    for (index, id) in upvalues.iter().enumerate() {
+                        function_c.declare(SymbolKind::Upvalue, index, id);
+                    }
                     function_c.upvalues = upvalues;
                     function_c.currentline = self.currentline;
                     function_c.native_functions = self.native_functions.clone();
+                    function_c.signatures = self.signatures.clone();
                     function_c.global = self.global.clone();
                     function_c.filepath = self.filepath.clone();
                     let mut arguments = vec![];
-                    for args in input.iter().rev() {
+                    for args in parameters.iter().rev() {
                         match args {
                             Token::Reg(id) => arguments.push(Token::RegStoreFast(id.to_string())),
                             _ => {
@@ -365,18 +536,24 @@ impl Compiler {
                         }
                     }
                     arguments.extend_from_slice(logic);
-                    let bytes = match function_c.compile_chunk(arguments.to_vec()) {
-                        Ok(chunk) => chunk,
-                        Err(error) => return Err(error),
-                    };
+                    let bytes = function_c.compile_chunk(arguments.to_vec())?;
 
                     let mut bytes = function_c.load_package(bytes);
+                    function_c.debug.chunk(
+                        ChunkKind::Closure,
+                        bound_name(&input, position),
+                        0,
+                        bytes.len(),
+                    );
+                    let symbols = std::mem::take(&mut function_c.symbols);
+                    function_c.debug.scope(0, bytes.len(), symbols);
                     output.push(Code::CLOSURE);
-                    let cast = bytes.len();
-                    let int = cast.to_ne_bytes();
-                    output.extend_from_slice(&int);
+                    code::write_uint(&mut output, bytes.len());
+                    self.debug.append(function_c.debug, output.len());
                     output.append(&mut bytes);
                     self.global = function_c.global.clone();
+                    self.currentline = function_c.currentline;
+                    self.mark_line(output.len());
                 }
                 Token::Doblock(_) => {
                     todo!();
@@ -391,18 +568,20 @@ impl Compiler {
                     // // output.extend_from_slice(&int);
                     // output.append(&mut bytes)
                 }
-                Token::CurrentFile(currentfile) => self.filepath = currentfile.clone(),
+                Token::CurrentFile(currentfile) => {
+                    self.filepath = currentfile.clone();
+                    self.mark_line(output.len());
+                }
                 Token::GlobalReg(id) => {
                     if let Some(index) = self.global.get_index(id.to_string()) {
                         output.push(Code::STOREGLOBAL);
-                        let bytes = index.to_ne_bytes();
-                        output.extend_from_slice(&bytes);
+                        code::write_uint(&mut output, index);
                     } else {
                         self.global.insert(id.to_string());
                         let index = self.global.len() - 1;
+                        self.declare(SymbolKind::Global, index, id);
                         output.push(Code::STOREGLOBAL);
-                        let bytes = index.to_ne_bytes();
-                        output.extend_from_slice(&bytes);
+                        code::write_uint(&mut output, index);
                     }
                 }
                 Token::Entry => {
@@ -415,6 +594,7 @@ impl Compiler {
                     function_c.upvalues = self.upvalues.clone();
                     function_c.currentline = self.currentline;
                     function_c.native_functions = self.native_functions.clone();
+                    function_c.signatures = self.signatures.clone();
                     function_c.global = self.global.clone();
                     function_c.filepath = self.filepath.clone();
                     function_c.variables = self.variables.clone();
@@ -432,16 +612,23 @@ impl Compiler {
                     }
 
                     arguments.extend_from_slice(logic);
-                    let mut bind = match function_c.compile_chunk(arguments.to_vec()) {
-                        Ok(chunk) => chunk,
-                        Err(error) => return Err(error),
-                    };
+                    let mut bind = function_c.compile_chunk(arguments.to_vec())?;
 
                     bind.pop();
+                    // only the bindings belong to the let, anything else it
+                    // declares lives in the enclosing scope
+                    let (bindings, symbols) = std::mem::take(&mut function_c.symbols)
+                        .into_iter()
+                        .partition(|symbol| symbol.kind == SymbolKind::Binding);
+                    self.symbols.extend::<Vec<Symbol>>(symbols);
+                    function_c.debug.scope(0, bind.len() + 1, bindings);
                     bind.insert(0, Code::NEWBINDING);
                     bind.push(Code::POPBINDING);
+                    self.debug.append(function_c.debug, output.len() + 1);
                     output.extend_from_slice(&bind);
                     self.global = function_c.global.clone();
+                    self.currentline = function_c.currentline;
+                    self.mark_line(output.len());
                 }
             }
         }
@@ -453,8 +640,8 @@ impl Compiler {
     fn load_package(&mut self, bytes: Vec<u8>) -> Vec<u8> {
         let mut package = vec![];
         package.push(Code::ALLOCATEREG);
-        let allocations = self.variables.len().to_ne_bytes();
-        package.extend_from_slice(&allocations);
+        code::write_uint(&mut package, self.variables.len());
+        self.debug.shift(package.len());
         package.extend_from_slice(&bytes);
         package
     }
@@ -463,8 +650,8 @@ impl Compiler {
     fn load_globals(&mut self, bytes: Vec<u8>) -> Vec<u8> {
         let mut package = vec![];
         package.push(Code::ALLOCATEGLOBAL);
-        let allocations = self.global.len().to_ne_bytes();
-        package.extend_from_slice(&allocations);
+        code::write_uint(&mut package, self.global.len());
+        self.debug.shift(package.len());
         package.extend_from_slice(&bytes);
         package
     }
@@ -1,4 +1,4 @@
-use common::{error::NovaError, tokens::Token};
+use common::error::NovaError;
 
 pub struct Core {
     lexer: lexer::Lexer,
@@ -6,7 +6,50 @@ pub struct Core {
     compiler: compiler::Compiler,
     vm: vm::Vm,
     filepath: String,
-    program: Vec<Token>,
+}
+
+/// Limits for the programs the core runs, None leaves a limit off. See the
+/// `Vm` methods of the same names.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    pub fuel: Option<u64>,
+    /// Measured from the call to `Core::limit`.
+    pub timeout: Option<std::time::Duration>,
+    pub max_call_depth: Option<usize>,
+    pub max_stack: Option<usize>,
+    pub max_heap: Option<usize>,
+}
+
+// packs a program into a container naming only the natives it calls, they
+// are renumbered in order of first use so the file does not depend on the
+// rest of the natives the host registers
+fn container(
+    mut program: Vec<u8>,
+    natives: &common::table::Table<String>,
+    debug: common::debug::DebugInfo,
+) -> Result<common::container::Container, NovaError> {
+    let mut disassembler = disassembler::new();
+    disassembler.native_functions = natives.clone();
+    let instructions = disassembler.dis(program.clone().into_iter())?;
+    let mut used = common::table::new();
+    for instruction in instructions {
+        if let Some(name) = instruction.native {
+            used.insert(name.clone());
+            let index = used.get_index(name).unwrap_or_default();
+            let slot = &mut program[instruction.offset + 1..instruction.offset + instruction.size];
+            if !common::code::patch_uint(slot, index) {
+                return Err(common::error::file_error(format!(
+                    "Could not renumber the native call at offset {}",
+                    instruction.offset
+                )));
+            }
+        }
+    }
+    Ok(common::container::new(
+        used.iter().cloned().collect(),
+        debug,
+        program,
+    ))
 }
 
 pub fn new() -> Core {
@@ -16,12 +59,192 @@ pub fn new() -> Core {
         compiler: compiler::new(),
         vm: vm::new(),
         filepath: String::new(),
-        program: vec![],
     }
 }
 
 impl Core {
-    pub fn dis(&mut self) {
+    /// Prints the disassembly of the open file, with `source` interleaving
+    /// the source lines each run of instructions came from.
+    pub fn dis(&mut self, source: bool) {
+        match self.disassemble() {
+            Ok(instructions) if source => print!(
+                "{}",
+                disassembler::render_source(&instructions, &self.compiler.debug)
+            ),
+            Ok(instructions) => print!("{}", disassembler::render_text(&instructions)),
+            Err(error) => {
+                error.show();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Prints the disassembly of the open file in the format `asm` reads.
+    pub fn dis_asm(&mut self) {
+        match self.disassemble() {
+            Ok(instructions) => print!("{}", disassembler::render_asm(&instructions)),
+            Err(error) => {
+                error.show();
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Assembles a text file written in the `dis_asm` format into a `.nvc`
+    /// file that `run_bytecode` can load.
+    pub fn asm(&mut self, filepath: &str, output: &str) {
+        let input = match std::fs::read_to_string(filepath) {
+            Ok(input) => input,
+            Err(_) => {
+                common::error::file_error(format!("file: {} could not be opened", filepath)).show();
+                std::process::exit(1);
+            }
+        };
+        let program = match self.assemble(&input) {
+            Ok(program) => program,
+            Err(error) => {
+                error.show();
+                std::process::exit(1);
+            }
+        };
+
+        let container = match container(
+            program,
+            &self.compiler.native_functions,
+            common::debug::new(),
+        ) {
+            Ok(container) => container,
+            Err(error) => {
+                error.show();
+                std::process::exit(1);
+            }
+        };
+        if let Err(error) = std::fs::write(output, container.encode()) {
+            common::error::file_error(format!("Could not write {}: {}", output, error)).show();
+            std::process::exit(1);
+        }
+    }
+
+    /// Turns text in the `dis_asm` format into bytecode, natives are named
+    /// as registered with this core.
+    pub fn assemble(&self, input: &str) -> Result<Vec<u8>, NovaError> {
+        let mut assembler = assembler::new();
+        assembler.native_functions = self.compiler.native_functions.clone();
+        assembler.asm(input)
+    }
+
+    /// Compiles the open file and decodes the result into instructions, for
+    /// tools that want more than the text `dis` prints.
+    pub fn disassemble(&mut self) -> Result<Vec<disassembler::Instruction>, NovaError> {
+        let program = self.lexer.parse()?;
+        let program = self.parser.parse(program.to_owned())?;
+        let program = self.compiler.compile(program, self.filepath.clone())?;
+        self.decode(program)
+    }
+
+    /// Decodes bytecode into instructions, natives are named as registered
+    /// with this core.
+    pub fn decode(&self, program: Vec<u8>) -> Result<Vec<disassembler::Instruction>, NovaError> {
+        let mut dis = disassembler::new();
+        dis.native_functions = self.compiler.native_functions.clone();
+        dis.dis(program.into_iter())
+    }
+
+    /// Logs each instruction `run`, `run_bytecode` and `eval` execute, None
+    /// turns tracing off again.
+    pub fn trace(&mut self, trace: Option<vm::trace::Trace>) {
+        self.vm.trace(trace)
+    }
+
+    pub fn limit(&mut self, limits: Limits) {
+        self.vm.fuel(limits.fuel);
+        self.vm.deadline(
+            limits
+                .timeout
+                .map(|timeout| std::time::Instant::now() + timeout),
+        );
+        self.vm.max_call_depth(limits.max_call_depth);
+        self.vm.max_stack(limits.max_stack);
+        self.vm.max_heap(limits.max_heap)
+    }
+
+    /// Profiles each instruction `run`, `run_bytecode` and `eval` execute
+    /// when `enabled`, read the results with `profile_table` and
+    /// `profile_stacks`.
+    pub fn profile(&mut self, enabled: bool) {
+        self.vm.profile(enabled)
+    }
+
+    pub fn profile_table(&self) -> Option<String> {
+        self.vm.profile_table()
+    }
+
+    pub fn profile_stacks(&self) -> Option<String> {
+        self.vm.profile_stacks()
+    }
+
+    /// Compiles the open file and runs it under `debugger`.
+    pub fn debug(&mut self, debugger: Box<dyn vm::debugger::Hook>) {
+        self.load();
+        self.vm.hook(Some(debugger));
+        if let Err(error) = self.vm.run() {
+            error.show();
+            std::process::exit(1);
+        }
+    }
+
+    pub fn add_function(&mut self, name: &str, function: vm::CallBack) {
+        self.compiler.native_functions.insert(name.to_string());
+        self.vm.add_native(name, function);
+    }
+
+    /// Adds a closure as a native, see `vm::Vm::add_native_closure`.
+    pub fn add_closure(
+        &mut self,
+        name: &str,
+        function: impl FnMut(&mut vm::state::State) -> Result<(), NovaError> + 'static,
+    ) {
+        self.compiler.native_functions.insert(name.to_string());
+        self.vm.add_native_closure(name, function);
+    }
+
+    /// Adds an ordinary Rust function as a native, see `vm::Vm::add_native_fn`.
+    pub fn add_fn<F, Args>(&mut self, name: &str, function: F)
+    where
+        F: vm::convert::NativeFn<Args> + 'static,
+    {
+        self.compiler.native_functions.insert(name.to_string());
+        self.compiler
+            .signatures
+            .insert(name.to_string(), common::signature::new(&F::params()));
+        self.vm.add_native_fn(name, function);
+    }
+
+    /// Declares the parameters of a registered native. Calls with more
+    /// arguments between their parentheses fail to compile, and the VM
+    /// checks the arguments against `params` before each call.
+    pub fn signature(&mut self, name: &str, params: &[common::signature::Type]) {
+        let signature = common::signature::new(params);
+        self.compiler
+            .signatures
+            .insert(name.to_string(), signature.clone());
+        self.vm.signature(name, signature);
+    }
+
+    /// Stores a value natives can reach through `State::context`.
+    pub fn set_context<T: std::any::Any>(&mut self, context: T) {
+        self.vm.state_mut().set_context(context)
+    }
+
+    /// Adds a native that can call the functions, closures and blocks it is
+    /// passed, see `vm::Vm::call`.
+    pub fn add_function_with_vm(&mut self, name: &str, function: vm::VmCallBack) {
+        self.compiler.native_functions.insert(name.to_string());
+        self.vm.add_native_with_vm(name, function);
+    }
+
+    /// Compiles the open file into a `.nvc` bytecode file at `output`.
+    pub fn build(&mut self, output: &str) {
         let program = match self.lexer.parse() {
             Ok(lexed) => lexed,
             Err(error) => {
@@ -45,26 +268,53 @@ impl Core {
             }
         };
 
-        let mut dis = disassembler::new();
-        dis.native_functions = self.compiler.native_functions.clone();
-        let _ = dis.dis(program.into_iter());
+        let container = match container(
+            program,
+            &self.compiler.native_functions,
+            self.compiler.debug.clone(),
+        ) {
+            Ok(container) => container,
+            Err(error) => {
+                error.show();
+                std::process::exit(1);
+            }
+        };
+        if let Err(error) = std::fs::write(output, container.encode()) {
+            common::error::file_error(format!("Could not write {}: {}", output, error)).show();
+            std::process::exit(1);
+        }
     }
-    pub fn add_function(&mut self, name: &str, function: vm::CallBack) {
-        self.compiler.native_functions.insert(name.to_string());
-        self.vm.native_functions.push(function);
+
+    /// Runs a `.nvc` file produced by `build`.
+    pub fn run_bytecode(&mut self, filepath: &str) {
+        let bytes = match std::fs::read(filepath) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                common::error::file_error(format!("file: {} could not be opened", filepath)).show();
+                std::process::exit(1);
+            }
+        };
+        if let Err(error) = self.vm.load(&bytes) {
+            error.show();
+            std::process::exit(1);
+        }
+        if let Err(error) = self.vm.run() {
+            error.show();
+            std::process::exit(1);
+        }
     }
 
     pub fn open_file(&mut self, filepath: &str) -> Result<(), String> {
         self.filepath = filepath.to_string();
+        self.parser.filepath(filepath);
         self.lexer.open_file(filepath)
     }
 
-    pub fn eval(&mut self, input: &str, repl: bool) -> Result<(), NovaError> {
+    /// Runs input on the same VM as every earlier call, so variables,
+    /// globals and the stack carry over between calls as in a REPL session.
+    pub fn eval(&mut self, input: &str) -> Result<(), NovaError> {
         self.lexer = lexer::new();
         self.parser = parser::new();
-        let mut vm = vm::new();
-
-        vm.native_functions = self.vm.native_functions.clone();
 
         self.lexer.insert_string(input);
 
@@ -82,50 +332,22 @@ impl Core {
             }
         };
 
-        let last = self.program.clone();
-
-        if repl {
-            let mut replprogram = vec![];
-            for t in self.program.clone() {
-                match t {
-                    Token::Call(ref call) => match call.as_str() {
-                        "println" => replprogram.push(Token::Pop),
-                        "print" => replprogram.push(Token::Pop),
-                        _ => replprogram.push(t),
-                    },
-                    _ => replprogram.push(t),
-                }
-            }
-            self.program = replprogram;
-        }
-
-        self.program.extend_from_slice(&program);
-
-        self.compiler.clear();
         let program = match self
             .compiler
-            .compile(self.program.clone(), self.filepath.clone())
+            .compile_increment(program.to_owned(), self.filepath.clone())
         {
             Ok(parsed) => parsed,
             Err(error) => {
-                self.program = last;
                 return Err(error);
             }
         };
 
-        vm.program(program);
-
-        match vm.run() {
-            Ok(_) => {}
-            Err(error) => {
-                self.program = last;
-                return Err(error);
-            }
-        };
-        Ok(())
+        self.vm.append(program, self.compiler.debug.clone());
+        self.vm.run()
     }
 
-    pub fn run(&mut self) {
+    // compiles the open file into the vm, exits on errors like run
+    fn load(&mut self) {
         //let start = Instant::now();
         let program = match self.lexer.parse() {
             Ok(lexed) => lexed,
@@ -178,6 +400,11 @@ impl Core {
         //println!("{}", rhexdump::hexdump(&program));
 
         self.vm.program(program);
+        self.vm.debug(self.compiler.debug.clone());
+    }
+
+    pub fn run(&mut self) {
+        self.load();
 
         //let start = Instant::now();
         match self.vm.run() {
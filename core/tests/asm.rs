@@ -0,0 +1,123 @@
+// `dis --asm` output must assemble back into the same program, and hand
+// written assembly must fail with errors rather than panics
+
+const EXAMPLES: &[(&str, &str)] = &[
+    (
+        "literals.nv",
+        r#"x = 1.5
+c = 'c'
+s = "a string ; not a comment"
+m = #["a" 1 "b" [1, -2]]
+println([x c s m])
+"#,
+    ),
+    (
+        "control.nv",
+        r#"mod recfib = [n]: {
+    if( n < 2 , {
+        return(n)
+    } {
+        recfib(n - 1) + recfib(n - 2)
+    })
+}
+xs = []
+for(i = 0, &i range(0 10) {
+    xs = push(xs recfib(i))
+})
+println(length(xs) > 5 && true)
+"#,
+    ),
+];
+
+fn natives(nova: &mut core::Core) {
+    nova.add_function("println", native::io::println);
+    nova.add_function("length", native::list::length);
+    nova.add_function("push", native::list::push);
+    nova.add_function("insert", native::list::insert);
+    nova.add_function("remove", native::list::remove);
+}
+
+fn round_trip(path: &str) {
+    let mut nova = core::new();
+    natives(&mut nova);
+    nova.open_file(path).unwrap();
+    let text = match nova.disassemble() {
+        Ok(instructions) => disassembler::render_asm(&instructions),
+        Err(error) => panic!("{} does not compile: {:?}", path, error),
+    };
+    let program = match nova.assemble(&text) {
+        Ok(program) => program,
+        Err(error) => panic!("{} does not assemble: {:?}", path, error),
+    };
+    let again = disassembler::render_asm(&nova.decode(program).unwrap());
+    assert_eq!(again, text, "{}", path);
+}
+
+#[test]
+fn std_round_trips() {
+    let std = concat!(env!("CARGO_MANIFEST_DIR"), "/../std");
+    let mut paths: Vec<_> = std::fs::read_dir(std)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "nv"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    for path in paths {
+        round_trip(path.to_str().unwrap())
+    }
+}
+
+#[test]
+fn examples_round_trip() {
+    let directory = std::env::temp_dir().join(format!("nova-asm-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    for (name, source) in EXAMPLES {
+        let path = directory.join(name);
+        std::fs::write(&path, source).unwrap();
+        round_trip(path.to_str().unwrap())
+    }
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+fn run(text: &str) -> Result<(), common::error::NovaError> {
+    let program = assembler::new().asm(text)?;
+    let mut vm = vm::new();
+    vm.append(program, common::debug::new());
+    vm.run()
+}
+
+#[test]
+fn hand_written_mistakes_are_errors() {
+    assert!(run("BYTE 1\nRET\n").is_ok());
+    assert!(run("BYTE 1\n").is_err());
+    assert!(run("ALLOCATEREG 0\nID 7\nRET\n").is_err());
+    assert!(run("ALLOCATEREG 0\nSTOREID 7\nBYTE 1\nASSIGN\nRET\n").is_err());
+    assert!(run("GLOBALID 3\nRET\n").is_err());
+    assert!(run("CID 0\nRET\n").is_err());
+    assert!(run("GETBIND 0\nRET\n").is_err());
+}
+
+#[test]
+fn jumps_past_the_end_are_errors() {
+    assert!(run("BLOCK 1\nRET\nRET\n").is_ok());
+    for jump in ["BLOCK", "FUNCTION", "CLOSURE"] {
+        assert!(run(&format!("{} 18446744073709551615\nRET\n", jump)).is_err());
+        assert!(run(&format!("{} 2\nRET\n", jump)).is_err());
+    }
+    assert!(run("FALSE\nJUMPIFFALSE 18446744073709551615\nRET\n").is_err());
+    assert!(run("TRUE\nSHORTOR 18446744073709551615\nRET\n").is_err());
+    assert!(run("FALSE\nSHORTAND 18446744073709551615\nRET\n").is_err());
+}
+
+#[test]
+fn huge_jumps_do_not_disassemble() {
+    let nova = core::new();
+    for jump in ["BLOCK", "FUNCTION", "CLOSURE"] {
+        let text = format!("{} 18446744073709551615\nRET\n", jump);
+        let program = nova.assemble(&text).unwrap();
+        assert!(nova.decode(program).is_err());
+    }
+    let program = nova.assemble("BLOCK 1\nRET\nRET\n").unwrap();
+    assert!(nova.decode(program).is_ok());
+}
@@ -0,0 +1,48 @@
+// the heap limit keeps its byte total up to date as values are dropped, so
+// it does not have to measure the whole heap to notice
+
+use common::error::NovaError;
+use vm::state::State;
+
+// fails unless the counted bytes match what the program holds
+fn accurate(state: &mut State) -> Result<(), NovaError> {
+    match (state.allocated(), state.heap_bytes()) {
+        (counted, held) if counted == held => Ok(()),
+        (counted, held) => Err(common::error::runetime_error(format!(
+            "counted {} bytes, the program holds {}",
+            counted, held
+        ))),
+    }
+}
+
+fn nova(max_heap: usize) -> core::Core {
+    let mut nova = core::new();
+    nova.add_function("accurate", accurate);
+    nova.add_function("push", native::list::push);
+    nova.limit(core::Limits {
+        max_heap: Some(max_heap),
+        ..Default::default()
+    });
+    nova
+}
+
+#[test]
+fn replaced_values_stop_counting() {
+    let mut nova = nova(1 << 20);
+    nova.eval("xs = [1 2 3]\nys = [\"a\" \"bc\"]\nxs = 0\naccurate()\n")
+        .unwrap();
+    nova.eval(
+        "mod f = []: {\n    zs = [1 2 3 4]\n    zs = [zs zs]\n    0\n}\nn = f()\naccurate()\n",
+    )
+    .unwrap();
+}
+
+#[test]
+fn programs_near_their_limit_keep_replacing_values() {
+    let mut nova = nova(2000 * vm::state::ITEM);
+    nova.eval(
+        "xs = range(1 1500)\nfor(i = 0, &i range(1 400) {\n    ys = [i i i i i]\n})\naccurate()\n",
+    )
+    .unwrap();
+    assert!(nova.eval("zs = range(1 600)\n").is_err());
+}
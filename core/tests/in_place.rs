@@ -0,0 +1,80 @@
+// `x = native(x ...)` hands the native the only reference to x's value, so
+// updates happen in place instead of copying the list on every call
+
+use common::error::NovaError;
+use std::rc::Rc;
+use vm::state::{State, VmBig};
+
+// passes a list through, failing if anything else still holds it
+fn unique(state: &mut State) -> Result<(), NovaError> {
+    match state.pop() {
+        Some(VmBig::List(list)) if Rc::strong_count(&list) == 1 => {
+            state.push(VmBig::List(list));
+            Ok(())
+        }
+        value => Err(common::error::runetime_error(format!(
+            "expected an unshared list, got {:?}",
+            value
+        ))),
+    }
+}
+
+// fails with its argument still on the stack
+fn refuse(_: &mut State) -> Result<(), NovaError> {
+    Err(common::error::runetime_error("refused".to_string()))
+}
+
+// runs a block with no arguments, then passes the list through
+fn apply(vm: &mut vm::Vm) -> Result<(), NovaError> {
+    match vm.state_mut().pop2() {
+        Some((block, list)) => {
+            vm.call(block, vec![])?;
+            vm.state_mut().push(list);
+            Ok(())
+        }
+        None => Err(common::error::runetime_error(
+            "apply takes 2 arguments".to_string(),
+        )),
+    }
+}
+
+fn nova() -> core::Core {
+    let mut nova = core::new();
+    nova.add_function("unique", unique);
+    nova.add_function("refuse", refuse);
+    nova.add_function("push", native::list::push);
+    nova.add_function("length", native::list::length);
+    nova.add_function_with_vm("apply", apply);
+    nova
+}
+
+#[test]
+fn assigned_lists_are_not_shared() {
+    let mut nova = nova();
+    nova.eval("xs = [1 2 3]\nxs = unique(xs)\n").unwrap();
+    nova.eval(
+        "mod fill = [n]: {\n    xs = []\n    for(i = 0, &i range(0 n) {\n        xs = push(xs i)\n        xs = unique(xs)\n    })\n    xs\n}\nys = fill(100)\nys = unique(ys)\n",
+    )
+    .unwrap();
+}
+
+#[test]
+fn lists_passed_elsewhere_stay_shared() {
+    let mut nova = nova();
+    assert!(nova.eval("xs = [1 2 3]\nys = unique(xs)\n").is_err());
+}
+
+#[test]
+fn failed_natives_give_the_value_back() {
+    let mut nova = nova();
+    nova.eval("xs = [1 2 3]\n").unwrap();
+    assert!(nova.eval("xs = refuse(xs)\n").is_err());
+    nova.eval("n = length(xs)\n").unwrap();
+}
+
+#[test]
+fn natives_running_nova_code_still_see_the_variable() {
+    let mut nova = nova();
+    nova.eval("xs = [1 2 3]\nxs = apply(xs, { length(xs) })\nn = length(xs)\n")
+        .unwrap();
+}
@@ -0,0 +1,50 @@
+// lines run one after another on the same VM, as the REPL does
+
+use common::error::NovaError;
+use vm::state::State;
+
+// fails unless nothing is left on the stack
+fn empty(state: &mut State) -> Result<(), NovaError> {
+    match state.stack_len() {
+        0 => Ok(()),
+        height => Err(common::error::runetime_error(format!(
+            "{} values left on the stack",
+            height
+        ))),
+    }
+}
+
+// takes the value on top of the stack away
+fn drop(state: &mut State) -> Result<(), NovaError> {
+    match state.pop() {
+        Some(_) => Ok(()),
+        None => Err(common::error::runetime_error("nothing to drop".to_string())),
+    }
+}
+
+fn nova() -> core::Core {
+    let mut nova = core::new();
+    nova.add_function("empty", empty);
+    nova.add_function("drop", drop);
+    nova.add_function("length", native::list::length);
+    nova
+}
+
+#[test]
+fn failed_lines_leave_nothing_behind() {
+    let mut nova = nova();
+    nova.eval("mod f = [x]: { x / 0 }\n").unwrap();
+    assert!(nova.eval("z = f(1) + 2\n").is_err());
+    nova.eval("empty()\n").unwrap();
+    assert!(nova.eval("xs = [1 2 3] length(\"a\")\n").is_err());
+    nova.eval("empty()\n").unwrap();
+}
+
+#[test]
+fn values_left_by_lines_that_succeed_are_kept() {
+    let mut nova = nova();
+    nova.eval("xs = [1 2 3]\nxs\n").unwrap();
+    assert!(nova.eval("z = 1 / 0\n").is_err());
+    assert!(nova.eval("empty()\n").is_err());
+    nova.eval("drop()\nempty()\n").unwrap();
+}
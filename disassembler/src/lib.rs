@@ -1,4 +1,9 @@
-use common::code::Code;
+use common::{
+    code::{Code, OperandKind},
+    debug::{DebugInfo, Symbol, SymbolKind},
+    error::NovaError,
+};
+use std::{collections::HashMap, fmt};
 
 pub fn new() -> Disassembler {
     Disassembler {
@@ -14,14 +19,352 @@ pub struct Disassembler {
     ip: usize,
 }
 
-impl Disassembler {
-    fn out(&self, output: &str) {
-        for _ in 0..self.depth.len() {
-            print!("  ")
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Int(i64),
+    Float(f64),
+    Char(char),
+    String(String),
+    Uint(usize),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Int(int) => write!(f, "{}", int),
+            Operand::Float(float) => write!(f, "{}", float),
+            Operand::Char(c) => write!(f, "{}", c),
+            Operand::String(string) => write!(f, "{}", string),
+            Operand::Uint(uint) => write!(f, "{}", uint),
+        }
+    }
+}
+
+/// One decoded instruction. `size` counts the opcode and its operand bytes,
+/// `depth` is how many block, function or closure bodies it is nested in,
+/// `native` the name of the function a NATIVE instruction calls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub offset: usize,
+    pub size: usize,
+    pub opcode: u8,
+    pub operands: Vec<Operand>,
+    pub depth: usize,
+    pub native: Option<String>,
+}
+
+fn json_string(string: &str) -> String {
+    let mut output = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+impl Operand {
+    pub fn to_json(&self) -> String {
+        match self {
+            Operand::Int(int) => int.to_string(),
+            Operand::Float(float) if float.is_finite() => format!("{:?}", float),
+            Operand::Float(float) => json_string(&float.to_string()),
+            Operand::Char(c) => json_string(&c.to_string()),
+            Operand::String(string) => json_string(string),
+            Operand::Uint(uint) => uint.to_string(),
+        }
+    }
+}
+
+impl Instruction {
+    pub fn name(&self) -> &'static str {
+        common::code::name(self.opcode).unwrap_or("UNKNOWN")
+    }
+
+    /// The instruction as `nova dis` prints it, without indentation.
+    pub fn text(&self) -> String {
+        let operand = match self.operands.first() {
+            Some(operand) => operand.to_string(),
+            None => String::new(),
+        };
+        match self.opcode {
+            Code::RET => "Return".to_string(),
+            Code::INTEGER | Code::BYTE => format!("Push Integer {}", operand),
+            Code::FLOAT => format!("Push Float {}", operand),
+            Code::ADD => "Add".to_string(),
+            Code::SUB => "Sub".to_string(),
+            Code::MUL => "Mul".to_string(),
+            Code::DIV => "Div".to_string(),
+            Code::STOREID => format!("Store ID {}", operand),
+            Code::ID => format!("ID {}", operand),
+            Code::ASSIGN => "Assign".to_string(),
+            Code::ALLOCATEREG => format!("Register allocation {}", operand),
+            Code::GROWREG => format!("Register growth {}", operand),
+            Code::TAKEID => format!("Take ID {}", operand),
+            Code::BLOCK => "Block:".to_string(),
+            Code::CALL => "Call".to_string(),
+            Code::DIRECTCALL => format!("Direct call {}", operand),
+            Code::NEWLIST => format!("Create list: size of {}", operand),
+            Code::NEWMAP => format!("Create map: size of {}", operand),
+            Code::TRUE => "Push True".to_string(),
+            Code::FALSE => "Push False".to_string(),
+            Code::STOREFASTID => format!("StoreFast ID {}", operand),
+            Code::FUNCTION => "Function:".to_string(),
+            Code::GTR => "Greater than".to_string(),
+            Code::LSS => "Less than".to_string(),
+            Code::GTE => "Greater or equal".to_string(),
+            Code::LTE => "Less or equal".to_string(),
+            Code::JUMPIFFALSE => format!("Jump if false: {}", operand),
+            Code::REC => "Recursive call".to_string(),
+            Code::WHEN => "When".to_string(),
+            Code::IF => "If".to_string(),
+            Code::EQUALS => "Equals".to_string(),
+            Code::NOTEQUALS => "Not equals".to_string(),
+            Code::MODULO => "Modulo".to_string(),
+            Code::REFID => format!("Referance ID {}", operand),
+            Code::CLOSURE => "Closure:".to_string(),
+            Code::CID => format!("Closure ID {}", operand),
+            Code::STRING => format!("Push String: {}", operand),
+            Code::FOR => "For".to_string(),
+            Code::BOUNCE => "Bounce".to_string(),
+            Code::RANGE => "Range".to_string(),
+            Code::NATIVE => match &self.native {
+                Some(function) => format!("Function: {}", function),
+                None => format!("Native {}", operand),
+            },
+            Code::ALLOCATEGLOBAL => format!("Global allocation {}", operand),
+            Code::GLOBALID => format!("Global ID {}", operand),
+            Code::STOREGLOBAL => format!("Store Global ID {}", operand),
+            Code::CHAR => format!("Push Char {}", operand),
+            Code::POP => "Pop".to_string(),
+            Code::NEG => "Neg".to_string(),
+            Code::BREAK => "Break".to_string(),
+            Code::NEWBINDING => "Create Bindings".to_string(),
+            Code::POPBINDING => "Remove Bindings".to_string(),
+            Code::GETBIND => format!("Get Binding {}", operand),
+            Code::STOREBIND => "Store New Binding".to_string(),
+            Code::LOOP => "Loop".to_string(),
+            Code::AND => "And".to_string(),
+            Code::OR => "Or".to_string(),
+            Code::NOT => "Not".to_string(),
+            Code::SHORTAND => format!("Jump if false, keep: {}", operand),
+            Code::SHORTOR => format!("Jump if true, keep: {}", operand),
+            opcode => format!("Unknown opcode {}", opcode),
+        }
+    }
+
+    /// The variable, global, upvalue or binding the operand refers to.
+    pub fn symbol<'a>(&self, debug: &'a DebugInfo) -> Option<&'a Symbol> {
+        let kind = match self.opcode {
+            Code::ID
+            | Code::STOREID
+            | Code::STOREFASTID
+            | Code::TAKEID
+            | Code::REFID
+            | Code::DIRECTCALL => SymbolKind::Register,
+            Code::GLOBALID | Code::STOREGLOBAL => SymbolKind::Global,
+            Code::CID => SymbolKind::Upvalue,
+            Code::GETBIND => SymbolKind::Binding,
+            _ => return None,
+        };
+        match self.operands.first() {
+            Some(Operand::Uint(index)) => debug.symbol(kind, *index, self.offset),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let operands: Vec<String> = self.operands.iter().map(Operand::to_json).collect();
+        let native = match &self.native {
+            Some(native) => json_string(native),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"offset\": {}, \"opcode\": {}, \"operands\": [{}], \"depth\": {}, \"native\": {}}}",
+            self.offset,
+            json_string(self.name()),
+            operands.join(", "),
+            self.depth,
+            native
+        )
+    }
+}
+
+/// Renders instructions one per line, indented by their depth.
+pub fn render_text(instructions: &[Instruction]) -> String {
+    let mut output = String::new();
+    for instruction in instructions {
+        for _ in 0..instruction.depth {
+            output.push_str("  ")
+        }
+        output.push_str(&instruction.text());
+        output.push('\n')
+    }
+    output
+}
+
+// import paths are recorded as written, relative to the importing file
+fn read_source(file: &str, debug: &DebugInfo) -> Option<Vec<String>> {
+    let mut candidates = vec![file.to_string()];
+    for known in debug.files.iter() {
+        if let Some(index) = known.rfind('/') {
+            candidates.push(format!("{}{}", &known[..=index], file))
+        }
+    }
+    candidates
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|source| {
+            source
+                .lines()
+                .map(|line| line.trim_end().to_string())
+                .collect()
+        })
+}
+
+/// Renders instructions like `render_text`, with each run of instructions
+/// preceded by the source line it was compiled from as a `;` comment, and
+/// block, function and closure headers labelled with the name they were
+/// bound to. Operands naming a variable are followed by its name.
+pub fn render_source(instructions: &[Instruction], debug: &DebugInfo) -> String {
+    let mut output = String::new();
+    let mut sources: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    let mut last = None;
+    for (index, instruction) in instructions.iter().enumerate() {
+        let indent = "  ".repeat(instruction.depth);
+        if let Some((line, file)) = debug.line_at(instruction.offset) {
+            if last != Some((line, file)) {
+                last = Some((line, file));
+                let source = sources
+                    .entry(file.to_string())
+                    .or_insert_with(|| read_source(file, debug))
+                    .as_ref()
+                    .and_then(|source| source.get(line.wrapping_sub(1)));
+                match source {
+                    Some(source) => output.push_str(&format!(
+                        "{}; {}:{} | {}\n",
+                        indent,
+                        file,
+                        line,
+                        source.trim()
+                    )),
+                    None => output.push_str(&format!("{}; {}:{}\n", indent, file, line)),
+                }
+            }
+        }
+
+        // a body with a name is a definition, told apart from the
+        // "Function:" of native calls
+        let name = match instruction.opcode {
+            Code::BLOCK | Code::FUNCTION | Code::CLOSURE => {
+                let body = instructions.get(index + 1).map(|next| next.offset);
+                debug
+                    .chunks
+                    .iter()
+                    .find(|chunk| Some(chunk.start) == body)
+                    .and_then(|chunk| chunk.name.as_ref())
+            }
+            _ => None,
+        };
+        output.push_str(&indent);
+        match name {
+            Some(name) => output.push_str(&format!("Define: {}", name)),
+            None => output.push_str(&instruction.text()),
+        }
+        if let Some(symbol) = instruction.symbol(debug) {
+            output.push_str(&format!(" ({})", symbol.name))
+        }
+        output.push('\n')
+    }
+    output
+}
+
+fn jump_target(instruction: &Instruction) -> Option<usize> {
+    match (
+        common::code::operand_kind(instruction.opcode),
+        instruction.operands.first(),
+    ) {
+        (OperandKind::Jump, Some(Operand::Uint(jump))) => {
+            (instruction.offset + instruction.size).checked_add(*jump)
+        }
+        _ => None,
+    }
+}
+
+/// Renders instructions in the format read by the assembler: opcode names
+/// from `Code`, quoted strings and chars, native functions by name and
+/// labels in place of jump distances. Labels are numbered in program order
+/// so the output of `assembler::asm` disassembles back to the same text.
+pub fn render_asm(instructions: &[Instruction]) -> String {
+    let end = match instructions.last() {
+        Some(last) => last.offset + last.size,
+        None => 0,
+    };
+    let mut targets: Vec<usize> = instructions
+        .iter()
+        .filter_map(jump_target)
+        .filter(|target| {
+            *target == end
+                || instructions
+                    .binary_search_by_key(target, |instruction| instruction.offset)
+                    .is_ok()
+        })
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let label = |target: usize| targets.binary_search(&target).ok();
+
+    let mut output = String::new();
+    for instruction in instructions {
+        if let Some(index) = label(instruction.offset) {
+            output.push_str(&format!("L{}:\n", index))
+        }
+        output.push_str(&"  ".repeat(instruction.depth));
+        output.push_str(instruction.name());
+        let operand = match (instruction.operands.first(), &instruction.native) {
+            (_, Some(native)) => Some(native.clone()),
+            (Some(Operand::Uint(jump)), _) => match jump_target(instruction).and_then(label) {
+                Some(index) => Some(format!("L{}", index)),
+                None => Some(jump.to_string()),
+            },
+            (Some(Operand::Float(float)), _) => Some(format!("{:?}", float)),
+            (Some(Operand::Char(c)), _) => Some(format!("{:?}", c)),
+            (Some(Operand::String(string)), _) => Some(format!("{:?}", string)),
+            (Some(operand), _) => Some(operand.to_string()),
+            (None, _) => None,
+        };
+        if let Some(operand) = operand {
+            output.push(' ');
+            output.push_str(&operand)
         }
-        println!("{}", output)
+        output.push('\n')
     }
+    if let Some(index) = label(end) {
+        output.push_str(&format!("L{}:\n", index))
+    }
+    output
+}
 
+/// Renders instructions as a JSON array with one object per instruction.
+pub fn render_json(instructions: &[Instruction]) -> String {
+    let lines: Vec<String> = instructions
+        .iter()
+        .map(|instruction| format!("  {}", instruction.to_json()))
+        .collect();
+    if lines.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+impl Disassembler {
     fn next(&mut self, input: &mut std::vec::IntoIter<u8>) -> Option<u8> {
         if let Some(index) = self.depth.last() {
             if self.ip == *index {
@@ -33,322 +376,93 @@ impl Disassembler {
         input.next()
     }
 
-    pub fn dis(
-        &mut self,
-        mut input: std::vec::IntoIter<u8>,
-    ) -> Result<(), common::error::NovaError> {
-        while let Some(code) = self.next(&mut input) {
-            match code {
-                Code::RET => {
-                    self.out("Return");
-                }
-                Code::INTEGER => {
-                    let int = i64::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Push Integer {}", int))
-                }
-                Code::BYTE => {
-                    let int = self.next(&mut input).unwrap() as i64;
-                    self.out(&format!("Push Integer {}", int))
-                }
-                Code::FLOAT => {
-                    let fl = f64::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Push Float {}", fl))
-                }
-                Code::ADD => self.out("Add"),
-                Code::SUB => self.out("Sub"),
-                Code::MUL => self.out("Mul"),
-                Code::DIV => self.out("Div"),
-                Code::STOREID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Store ID {}", index))
-                }
-                Code::ID => {
-                    let index = u16::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("ID {}", index))
-                }
-                Code::ASSIGN => self.out("Assign"),
-                Code::ALLOCATEREG => {
-                    let allocations = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Register allocation {}", allocations))
-                }
-                Code::BLOCK => {
-                    let jump = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.depth.push(self.ip + jump);
-                    self.out("Block:")
-                }
-                Code::CALL => self.out("Call"),
-                Code::DIRECTCALL => {
-                    let target = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Direct call {}", target))
-                }
-                Code::NEWLIST => {
-                    let size = u64::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Create list: size of {}", size))
-                }
-                Code::TRUE => self.out("Push True"),
-                Code::FALSE => self.out("Push False"),
-                Code::STOREFASTID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("StoreFast ID {}", index))
-                }
-                Code::FUNCTION => {
-                    let jump = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.depth.push(self.ip + jump);
-                    self.out("Function:")
-                }
-                Code::GTR => self.out("Greater than"),
-                Code::LSS => self.out("Less than"),
-                Code::JUMPIFFALSE => {
-                    let jump = u32::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Jump if false: {}", jump))
-                }
-                Code::REC => self.out("Recursive call"),
-                Code::WHEN => self.out("When"),
-                Code::IF => self.out("If"),
-                Code::EQUALS => self.out("Equals"),
-                Code::MODULO => self.out("Modulo"),
-                Code::REFID => {
-                    let index = u16::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Referance ID {}", index))
-                }
+    // operand decoders, see the encoding notes in common::code
+    fn byte(&mut self, input: &mut std::vec::IntoIter<u8>) -> Result<u8, NovaError> {
+        self.next(input).ok_or_else(|| self.malformed())
+    }
 
-                Code::CLOSURE => {
-                    let jump = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.depth.push(self.ip + jump);
-                    self.out("Closure:")
-                }
+    fn uint(&mut self, input: &mut std::vec::IntoIter<u8>) -> Result<usize, NovaError> {
+        common::code::read_uint(|| self.next(input)).ok_or_else(|| self.malformed())
+    }
 
-                Code::CID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Closure ID {}", index))
-                }
+    fn int(&mut self, input: &mut std::vec::IntoIter<u8>) -> Result<i64, NovaError> {
+        common::code::read_int(|| self.next(input)).ok_or_else(|| self.malformed())
+    }
 
-                Code::STRING => {
-                    let mut string = vec![];
-                    let size = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    for _ in 0..size {
-                        string.push(self.next(&mut input).unwrap());
-                    }
-                    let string = match String::from_utf8(string) {
-                        Ok(ok) => ok,
-                        Err(_) => todo!(),
-                    };
-                    self.out(&format!("Push String: {}", string))
-                }
+    fn float(&mut self, input: &mut std::vec::IntoIter<u8>) -> Result<f64, NovaError> {
+        common::code::read_float(|| self.next(input)).ok_or_else(|| self.malformed())
+    }
 
-                Code::FOR => self.out("For"),
+    fn char(&mut self, input: &mut std::vec::IntoIter<u8>) -> Result<char, NovaError> {
+        common::code::read_char(|| self.next(input)).ok_or_else(|| self.malformed())
+    }
 
-                Code::RANGE => self.out("Range"),
-                Code::NATIVE => {
-                    let index = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
+    fn malformed(&self) -> NovaError {
+        common::error::file_error(format!("Malformed operand before offset {}", self.ip))
+    }
 
-                    if let Some(function) = self.native_functions.retreive(index) {
-                        self.out(&format!("Function: {}", function))
+    pub fn dis(
+        &mut self,
+        mut input: std::vec::IntoIter<u8>,
+    ) -> Result<Vec<Instruction>, NovaError> {
+        let mut instructions = vec![];
+        loop {
+            let offset = self.ip;
+            let code = match self.next(&mut input) {
+                Some(code) => code,
+                None => break,
+            };
+            let operands = match common::code::operand_kind(code) {
+                OperandKind::Int => vec![Operand::Int(self.int(&mut input)?)],
+                OperandKind::Byte => vec![Operand::Int(self.byte(&mut input)? as i64)],
+                OperandKind::Float => vec![Operand::Float(self.float(&mut input)?)],
+                OperandKind::Char => vec![Operand::Char(self.char(&mut input)?)],
+                OperandKind::String => {
+                    let mut string = vec![];
+                    let size = self.uint(&mut input)?;
+                    for _ in 0..size {
+                        string.push(self.byte(&mut input)?);
+                    }
+                    match String::from_utf8(string) {
+                        Ok(ok) => vec![Operand::String(ok)],
+                        Err(_) => {
+                            return Err(common::error::file_error(format!(
+                                "String constant at offset {} is not valid utf-8",
+                                offset
+                            )))
+                        }
                     }
                 }
-                Code::ALLOCATEGLOBAL => {
-                    let size = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-                    self.out(&format!("Global allocation {}", size))
+                OperandKind::Uint | OperandKind::Jump => {
+                    vec![Operand::Uint(self.uint(&mut input)?)]
                 }
+                OperandKind::None => vec![],
+            };
 
-                Code::GLOBALID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-
-                    self.out(&format!("Global ID {}", index))
-                }
-
-                Code::STOREGLOBAL => {
-                    let index = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-
-                    self.out(&format!("Store Global ID {}", index))
-                }
-
-                Code::CHAR => {
-                    let c = self.next(&mut input).unwrap();
-
-                    self.out(&format!("Push Char {}", c as char))
+            let mut native = None;
+            match (code, operands.first()) {
+                (Code::BLOCK | Code::FUNCTION | Code::CLOSURE, Some(Operand::Uint(jump))) => {
+                    match self.ip.checked_add(*jump) {
+                        Some(end) => self.depth.push(end),
+                        None => return Err(self.malformed()),
+                    }
                 }
-
-                Code::POP => self.out("Pop"),
-                Code::NEG => self.out("Neg"),
-                Code::BREAK => self.out("Break"),
-                Code::NEWBINDING => self.out("Create Bindings"),
-                Code::POPBINDING => self.out("Remove Bindings"),
-                Code::GETBIND => {
-                    let index = usize::from_ne_bytes([
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                        self.next(&mut input).unwrap(),
-                    ]);
-
-                    self.out(&format!("Get Binding {}", index))
+                (Code::NATIVE, Some(Operand::Uint(index))) => {
+                    native = self.native_functions.retreive(*index).cloned()
                 }
-                Code::STOREBIND => self.out("Store New Binding"),
-                Code::LOOP => self.out("Loop"),
                 _ => {}
             }
+
+            instructions.push(Instruction {
+                offset,
+                size: self.ip - offset,
+                opcode: code,
+                operands,
+                depth: self.depth.len(),
+                native,
+            })
         }
 
-        Ok(())
+        Ok(instructions)
     }
 }
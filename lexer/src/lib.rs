@@ -108,6 +108,15 @@ impl Lexer {
     pub fn check_token(&mut self) -> Result<(), NovaError> {
         if let Some(token) = self.check_token_buffer() {
             match token {
+                Token::Reg(id) if matches!(id.as_str(), "and" | "or" | "not") => {
+                    let operator = match id.as_str() {
+                        "and" => Operator::And,
+                        "or" => Operator::Or,
+                        _ => Operator::Not,
+                    };
+                    self.push_token(Token::Position(self.line, self.row - id.len()));
+                    self.push_token(Token::Op(operator))
+                }
                 Token::Reg(id) => match self.last_token() {
                     Some(Token::Symbol('&')) => {
                         self.take_last_token();
@@ -157,6 +166,14 @@ impl Lexer {
         }
     }
 
+    // operators and punctuation carry their position so the parser can point
+    // at them in diagnostics
+    #[inline(always)]
+    fn push_symbol(&mut self, token: Token) {
+        self.push_token(Token::Position(self.line, self.row));
+        self.push_token(token)
+    }
+
     #[inline(always)]
     pub fn parse(&mut self) -> Result<&TokenList, NovaError> {
         if self.file.is_empty() {
@@ -247,7 +264,7 @@ impl Lexer {
                     continue;
                 } else {
                     self.is_parsing_char = false;
-                    if self.buffer.len() > 1 {
+                    if self.buffer.chars().count() > 1 {
                         return Err(common::error::lexer_error(
                             "Char cannot contain more than one character".to_string(),
                             "Try using double quotes instead, if you need a string".to_string(),
@@ -303,7 +320,7 @@ impl Lexer {
 
                 // Symbols
                 '+' | '*' | '/' | '(' | ')' | '<' | '>' | '`' | '~' | '@' | '%' | '^' | '&'
-                | ',' | '?' | ';' | '!' | '$' | '|' | '=' | '-' => {
+                | ',' | '?' | ';' | '!' | '$' | '|' | '=' | '-' | '#' => {
                     match self.check_token() {
                         Ok(_) => {}
                         Err(error) => return Err(error),
@@ -322,7 +339,7 @@ impl Lexer {
                                         chars.next();
                                         // check if list is last and make bindings
                                         match self.take_last_token() {
-                                            Some(Token::List(list)) => {
+                                            Some(Token::List(list, _)) => {
                                                 self.push_token(Token::Bindings(list))
                                             }
                                             _ => {
@@ -337,7 +354,7 @@ impl Lexer {
                                             }
                                         }
                                     }
-                                    _ => self.push_token(Token::Op(Operator::Sub)),
+                                    _ => self.push_symbol(Token::Op(Operator::Sub)),
                                 }
                             }
                             _ => {
@@ -346,7 +363,7 @@ impl Lexer {
                                         chars.next();
                                         // check if list is last and make bindings
                                         match self.take_last_token() {
-                                            Some(Token::List(list)) => {
+                                            Some(Token::List(list, _)) => {
                                                 self.push_token(Token::Bindings(list))
                                             }
                                             _ => {
@@ -361,7 +378,7 @@ impl Lexer {
                                             }
                                         }
                                     }
-                                    _ => self.push_token(Token::Op(Operator::Neg)),
+                                    _ => self.push_symbol(Token::Op(Operator::Neg)),
                                 }
                             }
                         },
@@ -370,18 +387,74 @@ impl Lexer {
                                 chars.next();
                                 self.is_parsing_comment = true;
                             }
-                            _ => self.push_token(Token::Op(Operator::Div)),
+                            _ => self.push_symbol(Token::Op(Operator::Div)),
                         },
                         '@' => self.push_token(Token::Symbol(char)),
-                        '?' => self.push_token(Token::Symbol(char)),
-                        '&' => self.push_token(Token::Symbol(char)),
-                        ',' => self.push_token(Token::Symbol(char)),
-                        '<' => self.push_token(Token::Op(Operator::Lss)),
-                        '>' => self.push_token(Token::Op(Operator::Gtr)),
-                        '!' => self.push_token(Token::Op(Operator::Not)),
-                        '%' => self.push_token(Token::Op(Operator::Mod)),
-                        '*' => self.push_token(Token::Op(Operator::Mul)),
-                        '+' => self.push_token(Token::Op(Operator::Add)),
+                        '#' => match chars.peek() {
+                            Some(&'[') => self.push_token(Token::Symbol(char)),
+                            _ => {
+                                return Err(common::error::lexer_error(
+                                    format!("Unknown char {}", char),
+                                    "Try #[key value] for a map".to_string(),
+                                    self.line,
+                                    self.row,
+                                    self.filepath.clone(),
+                                ));
+                            }
+                        },
+                        '?' => self.push_symbol(Token::Symbol(char)),
+                        '&' => match chars.peek() {
+                            Some(&'&') => {
+                                self.push_symbol(Token::Op(Operator::And));
+                                chars.next();
+                                self.row += 1;
+                            }
+                            _ => self.push_token(Token::Symbol(char)),
+                        },
+                        '|' => match chars.peek() {
+                            Some(&'|') => {
+                                self.push_symbol(Token::Op(Operator::Or));
+                                chars.next();
+                                self.row += 1;
+                            }
+                            _ => {
+                                return Err(common::error::lexer_error(
+                                    format!("Unknown char {}", char),
+                                    "Try using || for a logical or".to_string(),
+                                    self.line,
+                                    self.row,
+                                    self.filepath.clone(),
+                                ));
+                            }
+                        },
+                        ',' => self.push_symbol(Token::Symbol(char)),
+                        '<' => match chars.peek() {
+                            Some(&'=') => {
+                                self.push_symbol(Token::Op(Operator::Lte));
+                                chars.next();
+                                self.row += 1;
+                            }
+                            _ => self.push_symbol(Token::Op(Operator::Lss)),
+                        },
+                        '>' => match chars.peek() {
+                            Some(&'=') => {
+                                self.push_symbol(Token::Op(Operator::Gte));
+                                chars.next();
+                                self.row += 1;
+                            }
+                            _ => self.push_symbol(Token::Op(Operator::Gtr)),
+                        },
+                        '!' => match chars.peek() {
+                            Some(&'=') => {
+                                self.push_symbol(Token::Op(Operator::NotEquals));
+                                chars.next();
+                                self.row += 1;
+                            }
+                            _ => self.push_symbol(Token::Op(Operator::Invert)),
+                        },
+                        '%' => self.push_symbol(Token::Op(Operator::Mod)),
+                        '*' => self.push_symbol(Token::Op(Operator::Mul)),
+                        '+' => self.push_symbol(Token::Op(Operator::Add)),
 
                         '(' => {
                             // check for function calls
@@ -389,18 +462,18 @@ impl Lexer {
                                 Some(Token::Reg(caller)) => match caller.as_str() {
                                     "import" => {}
                                     "break" => self.push_token(Token::Op(Operator::Break)),
-                                    _ => self.push_token(Token::Call(caller)),
+                                    _ => self.push_token(Token::Call(caller, 0)),
                                 },
                                 Some(Token::Symbol(')')) => {}
                                 Some(token) => self.push_token(token),
                                 None => {}
                             }
-                            self.push_token(Token::Symbol(char));
+                            self.push_symbol(Token::Symbol(char));
                             self.frames.push(LexFrame::Paren(self.line, self.row))
                         }
                         ')' => {
                             if let Some(LexFrame::Paren(_, _)) = self.frames.pop() {
-                                self.push_token(Token::Symbol(char));
+                                self.push_symbol(Token::Symbol(char));
                             } else {
                                 self.frames.push(LexFrame::Paren(self.line, self.row));
                             }
@@ -409,26 +482,26 @@ impl Lexer {
                             match chars.peek() {
                                 Some(&'=') => {
                                     // push equality
-                                    self.push_token(Token::Op(Operator::Equals));
+                                    self.push_symbol(Token::Op(Operator::Equals));
                                     chars.next();
                                     self.row += 1;
                                 }
                                 _ => match self.take_last_token() {
                                     Some(Token::Reg(id)) => {
                                         self.push_token(Token::RegStore(id));
-                                        self.push_token(Token::Op(Operator::Assign));
+                                        self.push_symbol(Token::Op(Operator::Assign));
                                     }
                                     Some(Token::RegRef(id)) => {
                                         self.push_token(Token::RegRef(id));
-                                        self.push_token(Token::Op(Operator::Assign));
+                                        self.push_symbol(Token::Op(Operator::Assign));
                                     }
                                     Some(Token::GlobalReg(id)) => {
                                         self.push_token(Token::GlobalReg(id));
-                                        self.push_token(Token::Op(Operator::Assign));
+                                        self.push_symbol(Token::Op(Operator::Assign));
                                     }
                                     Some(Token::Symbol(')')) => {
                                         self.push_token(Token::Symbol(')'));
-                                        self.push_token(Token::Op(Operator::Assign));
+                                        self.push_symbol(Token::Op(Operator::Assign));
                                     }
                                     _ => {
                                         return Err(common::error::lexer_error(
@@ -519,8 +592,11 @@ impl Lexer {
                                     self.push_token(Token::Arguments(block.to_vec()));
                                     chars.next();
                                     self.row += 1;
+                                } else if let Some(Token::Symbol('#')) = self.last_token() {
+                                    self.take_last_token();
+                                    self.push_token(Token::Map(block.to_vec(), 0));
                                 } else {
-                                    self.push_token(Token::List(block.to_vec()));
+                                    self.push_token(Token::List(block.to_vec(), 0));
                                 }
                             }
                         }
@@ -567,7 +643,7 @@ impl Lexer {
 
         // println!("{}", self.buffer);
 
-        self.output.get(0).ok_or_else(|| {
+        self.output.first().ok_or_else(|| {
             common::error::file_error(
                 "Failed to parse, Could not retrieve last index on output".to_string(),
             )
@@ -1,4 +1,5 @@
 use common::error::NovaError;
+use std::rc::Rc;
 use vm::state::{self, VmBig};
 
 pub fn println(state: &mut state::State) -> Result<(), NovaError> {
@@ -16,6 +17,12 @@ pub fn println(state: &mut state::State) -> Result<(), NovaError> {
             VmBig::List(array) => {
                 println!("{:?}", array)
             }
+            VmBig::Map(map) => {
+                println!("{:?}", map)
+            }
+            VmBig::UserData(data) => {
+                println!("{}", data)
+            }
             VmBig::Bool(bool) => {
                 println!("{bool}")
             }
@@ -50,6 +57,12 @@ pub fn print(state: &mut state::State) -> Result<(), NovaError> {
             VmBig::List(array) => {
                 print!("{:?}", array)
             }
+            VmBig::Map(map) => {
+                print!("{:?}", map)
+            }
+            VmBig::UserData(data) => {
+                print!("{}", data)
+            }
             VmBig::Bool(bool) => {
                 print!("{bool}")
             }
@@ -71,6 +84,7 @@ pub fn print(state: &mut state::State) -> Result<(), NovaError> {
 pub fn readln(state: &mut state::State) -> Result<(), NovaError> {
     let mut line = String::new();
     std::io::stdin().read_line(&mut line).unwrap();
-    state.push(VmBig::String(line));
+    state.allocate(line.len())?;
+    state.push(VmBig::String(Rc::new(line)));
     Ok(())
 }
@@ -1,3 +1,4 @@
 pub mod io;
 pub mod list;
+pub mod map;
 pub mod random;
@@ -1,5 +1,9 @@
 use common::error::NovaError;
-use vm::state::{self, VmBig, VmSmall};
+use std::rc::Rc;
+use vm::{
+    state::{self, VmBig, VmSmall},
+    Vm,
+};
 
 pub fn length(state: &mut state::State) -> Result<(), NovaError> {
     if let Some(list) = state.pop() {
@@ -19,8 +23,9 @@ pub fn push(state: &mut state::State) -> Result<(), NovaError> {
     if let Some(args) = state.pop2() {
         match args {
             (item, VmBig::List(mut list)) => {
-                list.push(item);
+                Rc::make_mut(&mut list).push(item);
                 state.push(VmBig::List(list));
+                state.allocate(state::ITEM)?;
             }
             _ => {
                 return Err(common::error::runetime_error(
@@ -36,7 +41,7 @@ pub fn pop(state: &mut state::State) -> Result<(), NovaError> {
     if let Some(args) = state.pop() {
         match args {
             VmBig::List(mut list) => {
-                list.pop();
+                Rc::make_mut(&mut list).pop();
                 state.push(VmBig::List(list));
             }
             _ => {
@@ -70,8 +75,9 @@ pub fn insert(state: &mut state::State) -> Result<(), NovaError> {
     if let (Some(arg1),Some(arg2),Some(arg3))= (state.pop(),state.pop_fast(),state.pop()) {
         match (arg1,arg2,arg3) {
             (item,VmSmall::Int(index),VmBig::List(mut list)) => {
-                list.insert(index as usize, item);
-                state.push(VmBig::List(list.clone()));
+                Rc::make_mut(&mut list).insert(index as usize, item);
+                state.push(VmBig::List(list));
+                state.allocate(state::ITEM)?;
             }
             _ => {
                 return Err(common::error::runetime_error(
@@ -88,8 +94,8 @@ pub fn remove(state: &mut state::State) -> Result<(), NovaError> {
     if let (Some(arg1),Some(arg2))= (state.pop_fast(),state.pop()) {
         match (arg1,arg2) {
             (VmSmall::Int(index),VmBig::List(mut list)) => {
-                list.remove(index as usize);
-                state.push(VmBig::List(list.clone()));
+                Rc::make_mut(&mut list).remove(index as usize);
+                state.push(VmBig::List(list));
             }
             _ => {
                 return Err(common::error::runetime_error(
@@ -100,3 +106,119 @@ pub fn remove(state: &mut state::State) -> Result<(), NovaError> {
     }
     Ok(())
 }
+
+// calls a function that has to answer with a Bool
+fn test(
+    vm: &mut Vm,
+    name: &str,
+    function: &VmBig,
+    arguments: Vec<VmBig>,
+) -> Result<bool, NovaError> {
+    match vm.call(function.clone(), arguments)? {
+        VmBig::Bool(bool) => Ok(bool),
+        a => Err(common::error::runetime_error(format!(
+            "{} expected the function to return Bool, found {:?}",
+            name, a
+        ))),
+    }
+}
+
+// list function
+pub fn map(vm: &mut Vm) -> Result<(), NovaError> {
+    match vm.state_mut().pop2() {
+        Some((function, VmBig::List(list))) => {
+            vm.state_mut().allocate(list.len() * state::ITEM)?;
+            let mut mapped = Vec::with_capacity(list.len());
+            for item in list.iter() {
+                mapped.push(vm.call(function.clone(), vec![item.clone()])?);
+            }
+            vm.state_mut().push(VmBig::List(Rc::new(mapped)));
+        }
+        _ => {
+            return Err(common::error::runetime_error(
+                "Not enough arguments for map".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// list test
+pub fn filter(vm: &mut Vm) -> Result<(), NovaError> {
+    match vm.state_mut().pop2() {
+        Some((function, VmBig::List(list))) => {
+            vm.state_mut().allocate(list.len() * state::ITEM)?;
+            let mut kept = vec![];
+            for item in list.iter() {
+                if test(vm, "filter", &function, vec![item.clone()])? {
+                    kept.push(item.clone())
+                }
+            }
+            vm.state_mut().push(VmBig::List(Rc::new(kept)));
+        }
+        _ => {
+            return Err(common::error::runetime_error(
+                "Not enough arguments for filter".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// stable merge sort, taking from the right half only when its item is less
+fn merge_sort(vm: &mut Vm, less: &VmBig, mut items: Vec<VmBig>) -> Result<Vec<VmBig>, NovaError> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(vm, less, items)?;
+    let right = merge_sort(vm, less, right)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        let arguments = vec![b.clone(), a.clone()];
+        let next = match test(vm, "sort_by", less, arguments)? {
+            true => right.next(),
+            false => left.next(),
+        };
+        merged.extend(next)
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+// list less, where less(a b) tells whether a goes before b
+pub fn sort_by(vm: &mut Vm) -> Result<(), NovaError> {
+    match vm.state_mut().pop2() {
+        Some((less, VmBig::List(list))) => {
+            vm.state_mut().allocate(list.len() * state::ITEM)?;
+            let sorted = merge_sort(vm, &less, Rc::unwrap_or_clone(list))?;
+            vm.state_mut().push(VmBig::List(Rc::new(sorted)));
+        }
+        _ => {
+            return Err(common::error::runetime_error(
+                "Not enough arguments for sort_by".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// list function
+pub fn each(vm: &mut Vm) -> Result<(), NovaError> {
+    match vm.state_mut().pop2() {
+        Some((function, VmBig::List(list))) => {
+            for item in list.iter() {
+                vm.call(function.clone(), vec![item.clone()])?;
+            }
+        }
+        _ => {
+            return Err(common::error::runetime_error(
+                "Not enough arguments for each".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
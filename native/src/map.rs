@@ -0,0 +1,112 @@
+use common::error::NovaError;
+use std::rc::Rc;
+use vm::state::{self, VmBig};
+
+// map key
+pub fn get(state: &mut state::State) -> Result<(), NovaError> {
+    if let Some(args) = state.pop2() {
+        match args {
+            (key, VmBig::Map(map)) => {
+                let key = key.to_key()?;
+                state.push(map.get(&key).cloned().unwrap_or(VmBig::None));
+            }
+            _ => {
+                return Err(common::error::runetime_error(
+                    "Not enough arguments for get".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// map key value
+pub fn set(state: &mut state::State) -> Result<(), NovaError> {
+    if let (Some(arg1), Some(arg2), Some(arg3)) = (state.pop(), state.pop(), state.pop()) {
+        match (arg1, arg2, arg3) {
+            (value, key, VmBig::Map(mut map)) => {
+                Rc::make_mut(&mut map).insert(key.to_key()?, value);
+                state.push(VmBig::Map(map));
+                state.allocate(state::ENTRY)?;
+            }
+            _ => {
+                return Err(common::error::runetime_error(
+                    "Not enough arguments for set".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// map key
+pub fn has(state: &mut state::State) -> Result<(), NovaError> {
+    if let Some(args) = state.pop2() {
+        match args {
+            (key, VmBig::Map(map)) => {
+                let key = key.to_key()?;
+                state.push(VmBig::Bool(map.contains_key(&key)));
+            }
+            _ => {
+                return Err(common::error::runetime_error(
+                    "Not enough arguments for has".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn keys(state: &mut state::State) -> Result<(), NovaError> {
+    if let Some(map) = state.pop() {
+        match map {
+            VmBig::Map(map) => {
+                state.allocate(map.len() * state::ITEM)?;
+                state.push(VmBig::List(Rc::new(
+                    map.keys().map(|key| key.to_value()).collect(),
+                )))
+            }
+            _ => {
+                return Err(common::error::runetime_error(
+                    "Not enough arguments for keys".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn values(state: &mut state::State) -> Result<(), NovaError> {
+    if let Some(map) = state.pop() {
+        match map {
+            VmBig::Map(map) => {
+                state.allocate(map.len() * state::ITEM)?;
+                state.push(VmBig::List(Rc::new(map.values().cloned().collect())))
+            }
+            _ => {
+                return Err(common::error::runetime_error(
+                    "Not enough arguments for values".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// map key
+pub fn delete(state: &mut state::State) -> Result<(), NovaError> {
+    if let Some(args) = state.pop2() {
+        match args {
+            (key, VmBig::Map(mut map)) => {
+                Rc::make_mut(&mut map).remove(&key.to_key()?);
+                state.push(VmBig::Map(map));
+            }
+            _ => {
+                return Err(common::error::runetime_error(
+                    "Not enough arguments for delete".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,20 +1,12 @@
 use common::error::NovaError;
 use rand::Rng;
-use vm::state::{self, VmSmall};
 
-pub fn random(state: &mut state::State) -> Result<(), NovaError> {
-    if let Some((arg1, arg2)) = state.pop_fast2() {
-        match (arg2, arg1) {
-            (VmSmall::Int(start), VmSmall::Int(end)) => {
-                let mut rng = rand::thread_rng();
-                state.push_fast(VmSmall::Int(rng.gen_range(start..=end)))
-            }
-            _ => {
-                return Err(common::error::runetime_error(
-                    "Not enough arguments for random".to_string(),
-                ));
-            }
-        }
+pub fn random(start: i64, end: i64) -> Result<i64, NovaError> {
+    if start > end {
+        return Err(common::error::runetime_error(format!(
+            "random expected start to be at most end, found {} and {}",
+            start, end
+        )));
     }
-    Ok(())
+    Ok(rand::thread_rng().gen_range(start..=end))
 }
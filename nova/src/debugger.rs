@@ -0,0 +1,225 @@
+use common::debug::SymbolKind;
+use common::error::NovaError;
+use std::{collections::HashMap, io::Write};
+use vm::{
+    debugger::{Breakpoint, Debugger, Hook, Step, Stop},
+    state::VmBig,
+    Vm,
+};
+
+const HELP: &str = "\
+break LINE | FILE:LINE | NAME   set a breakpoint (b)
+delete N                        remove breakpoint N (d)
+breakpoints                     list breakpoints
+continue                        run to the next breakpoint (c)
+step                            run to the next line, entering calls (s)
+next                            run to the next line, stepping over calls (n)
+out                             run until the current frame returns (o)
+where                           show the current line (w)
+backtrace                       show the call frames (bt)
+stack [COUNT]                   show the top of the operand stack
+registers                       show the registers of the current call (r)
+upvalues                        show the values captured by the current closure
+bindings                        show the current bindings
+globals                         show the globals
+print NAME                      show a variable or global by name (p)
+quit                            stop the program (q)";
+
+pub fn new() -> Console {
+    Console {
+        debugger: vm::debugger::new(),
+        sources: HashMap::new(),
+    }
+}
+
+/// Command line front end for `vm::debugger`, used by `nova debug`.
+pub struct Console {
+    debugger: Debugger,
+    sources: HashMap<String, Option<Vec<String>>>,
+}
+
+fn breakpoint(argument: &str) -> Breakpoint {
+    if let Ok(line) = argument.parse() {
+        return Breakpoint::Line(None, line);
+    }
+    match argument.rsplit_once(':') {
+        Some((file, line)) if !file.is_empty() && line.parse::<usize>().is_ok() => {
+            Breakpoint::Line(Some(file.to_string()), line.parse().unwrap_or_default())
+        }
+        _ => Breakpoint::Function(argument.to_string()),
+    }
+}
+
+fn describe(breakpoint: &Breakpoint) -> String {
+    match breakpoint {
+        Breakpoint::Line(Some(file), line) => format!("{}:{}", file, line),
+        Breakpoint::Line(None, line) => format!("line {}", line),
+        Breakpoint::Function(name) => name.clone(),
+    }
+}
+
+// values of one kind of symbol, as the instruction about to run sees them
+fn values(vm: &Vm, kind: SymbolKind) -> &[VmBig] {
+    match kind {
+        SymbolKind::Register => vm.state().registers(),
+        SymbolKind::Global => vm.state().globals(),
+        SymbolKind::Upvalue => vm.state().upvalues(),
+        SymbolKind::Binding => vm.state().bindings(),
+    }
+}
+
+fn show(vm: &Vm, values: &[VmBig], kind: Option<SymbolKind>) {
+    if values.is_empty() {
+        println!("  (empty)");
+    }
+    for (index, value) in values.iter().enumerate() {
+        let name = kind
+            .and_then(|kind| vm.symbol(kind, index))
+            .map(|symbol| symbol.name.as_str())
+            .unwrap_or_default();
+        println!("  {:>3} {:<16} {:?}", index, name, value)
+    }
+}
+
+impl Console {
+    fn source(&mut self, file: &str, line: usize) -> Option<String> {
+        self.sources
+            .entry(file.to_string())
+            .or_insert_with(|| {
+                std::fs::read_to_string(file)
+                    .ok()
+                    .map(|source| source.lines().map(|line| line.to_string()).collect())
+            })
+            .as_ref()?
+            .get(line.checked_sub(1)?)
+            .map(|line| line.trim().to_string())
+    }
+
+    fn position(&mut self, vm: &Vm) {
+        let frame = vm.backtrace().into_iter().next();
+        match vm.debug_info().line_at(vm.current_instruction()) {
+            Some((line, file)) => {
+                let file = file.to_string();
+                let function = frame.map(|frame| frame.function).unwrap_or_default();
+                println!("{}:{} in {}", file, line, function);
+                if let Some(source) = self.source(&file, line) {
+                    println!("{:>5} | {}", line, source)
+                }
+            }
+            None => println!("at offset {}", vm.current_instruction()),
+        }
+    }
+
+    fn print(&self, vm: &Vm, name: &str) {
+        // innermost names first, the way the compiler resolves them
+        let kinds = [
+            SymbolKind::Binding,
+            SymbolKind::Register,
+            SymbolKind::Upvalue,
+            SymbolKind::Global,
+        ];
+        let found = kinds.into_iter().find_map(|kind| {
+            let values = values(vm, kind);
+            values.iter().enumerate().find_map(|(index, value)| {
+                let symbol = vm.symbol(kind, index)?;
+                (symbol.name == name).then_some((value, symbol.line))
+            })
+        });
+        match found {
+            Some((value, line)) => println!("{} = {:?} (declared on line {})", name, value, line),
+            None => println!("No variable or global named {} here", name),
+        }
+    }
+
+    // reads commands until one resumes the program
+    fn prompt(&mut self, vm: &Vm) -> Step {
+        loop {
+            print!("(nova) ");
+            std::io::stdout().flush().unwrap_or_default();
+            let mut input = String::new();
+            match std::io::stdin().read_line(&mut input) {
+                Ok(0) | Err(_) => std::process::exit(0),
+                Ok(_) => {}
+            }
+            let mut words = input.split_whitespace();
+            let command = words.next().unwrap_or_default();
+            let argument = words.next();
+            match (command, argument) {
+                ("", _) => {}
+                ("c" | "continue", _) => return Step::Continue,
+                ("s" | "step", _) => return Step::Into,
+                ("n" | "next", _) => return Step::over(vm),
+                ("o" | "out", _) => return Step::out(vm),
+                ("b" | "break", Some(argument)) => {
+                    let breakpoint = breakpoint(argument);
+                    println!(
+                        "Breakpoint {} at {}",
+                        self.debugger.breakpoints.len() + 1,
+                        describe(&breakpoint)
+                    );
+                    self.debugger.breakpoints.push(breakpoint)
+                }
+                ("d" | "delete", Some(argument)) => match argument.parse::<usize>() {
+                    Ok(number) if number >= 1 && number <= self.debugger.breakpoints.len() => {
+                        self.debugger.breakpoints.remove(number - 1);
+                    }
+                    _ => println!("No breakpoint {}", argument),
+                },
+                ("breakpoints", _) => {
+                    for (index, breakpoint) in self.debugger.breakpoints.iter().enumerate() {
+                        println!("  {} {}", index + 1, describe(breakpoint))
+                    }
+                }
+                ("w" | "where", _) => self.position(vm),
+                ("bt" | "backtrace", _) => {
+                    for frame in vm.backtrace() {
+                        println!("  {} at {}:{}", frame.function, frame.file, frame.line)
+                    }
+                }
+                ("stack", count) => {
+                    let count = count.and_then(|count| count.parse().ok()).unwrap_or(16);
+                    let (below, values) = vm.state().top(count);
+                    if below > 0 {
+                        println!("  ({} more below)", below)
+                    }
+                    show(vm, &values, None)
+                }
+                ("r" | "registers", _) => {
+                    let kind = SymbolKind::Register;
+                    show(vm, values(vm, kind), Some(kind))
+                }
+                ("upvalues", _) => show(
+                    vm,
+                    values(vm, SymbolKind::Upvalue),
+                    Some(SymbolKind::Upvalue),
+                ),
+                ("bindings", _) => show(
+                    vm,
+                    values(vm, SymbolKind::Binding),
+                    Some(SymbolKind::Binding),
+                ),
+                ("globals", _) => {
+                    show(vm, values(vm, SymbolKind::Global), Some(SymbolKind::Global))
+                }
+                ("p" | "print", Some(name)) => self.print(vm, name),
+                ("q" | "quit", _) => std::process::exit(0),
+                ("h" | "help", _) => println!("{}", HELP),
+                _ => println!("Unknown command {}, try help", input.trim()),
+            }
+        }
+    }
+}
+
+impl Hook for Console {
+    fn before(&mut self, vm: &Vm) -> Result<(), NovaError> {
+        match self.debugger.check(vm) {
+            Some(Stop::Breakpoint(index)) => println!("Breakpoint {}", index + 1),
+            Some(Stop::Step) => {}
+            None => return Ok(()),
+        }
+        self.position(vm);
+        let step = self.prompt(vm);
+        self.debugger.resume(step);
+        Ok(())
+    }
+}
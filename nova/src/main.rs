@@ -1,3 +1,124 @@
+mod debugger;
+
+use common::signature::Type;
+
+// --profile [--profile-stacks path], returns the options left for tracing
+fn profile_options(options: Vec<String>) -> (bool, Option<String>, Vec<String>) {
+    let mut profile = false;
+    let mut stacks = None;
+    let mut rest = vec![];
+    let mut options = options.into_iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--profile" => profile = true,
+            "--profile-stacks" => match options.next() {
+                Some(path) => {
+                    profile = true;
+                    stacks = Some(path)
+                }
+                None => rest.push(option),
+            },
+            _ => rest.push(option),
+        }
+    }
+    (profile, stacks, rest)
+}
+
+// --fuel count --timeout milliseconds --max-depth calls --max-stack values
+// --max-heap bytes, returns the options left for profiling
+fn limit_options(options: Vec<String>) -> Result<(core::Limits, Vec<String>), String> {
+    let mut limits = core::Limits::default();
+    let mut rest = vec![];
+    let mut options = options.into_iter();
+    while let Some(option) = options.next() {
+        let needs = match option.as_str() {
+            "--fuel" => "an instruction count",
+            "--timeout" => "a number of milliseconds",
+            "--max-depth" => "a number of calls",
+            "--max-stack" => "a number of values",
+            "--max-heap" => "a number of bytes",
+            _ => {
+                rest.push(option);
+                continue;
+            }
+        };
+        let value: usize = match options.next().map(|value| value.parse()) {
+            Some(Ok(value)) => value,
+            _ => return Err(format!("{} needs {}", option, needs)),
+        };
+        match option.as_str() {
+            "--fuel" => limits.fuel = Some(value as u64),
+            "--timeout" => limits.timeout = Some(std::time::Duration::from_millis(value as u64)),
+            "--max-depth" => limits.max_call_depth = Some(value),
+            "--max-stack" => limits.max_stack = Some(value),
+            _ => limits.max_heap = Some(value),
+        }
+    }
+    Ok((limits, rest))
+}
+
+// --trace [--trace-file path] [--trace-op NAME,NAME] [--trace-range start..end]
+// [--trace-stack count], any of the --trace-* options implies --trace
+fn trace_options(options: &[String]) -> Result<Option<vm::trace::Trace>, String> {
+    if options.is_empty() {
+        return Ok(None);
+    }
+    let mut file = None;
+    let mut opcodes = vec![];
+    let mut range = None;
+    let mut stack = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        if option == "--trace" {
+            continue;
+        }
+        let value = match options.next() {
+            Some(value) => value,
+            None => return Err(format!("Missing value for {}", option)),
+        };
+        match option.as_str() {
+            "--trace-file" => file = Some(value.clone()),
+            "--trace-op" => {
+                for name in value.split(',') {
+                    match common::code::opcode(&name.to_ascii_uppercase()) {
+                        Some(opcode) => opcodes.push(opcode),
+                        None => return Err(format!("Unknown opcode {}", name)),
+                    }
+                }
+            }
+            "--trace-range" => {
+                let bounds = value.split_once("..").and_then(|(start, end)| {
+                    Some(start.parse::<usize>().ok()?..end.parse::<usize>().ok()?)
+                });
+                match bounds {
+                    Some(bounds) => range = Some(bounds),
+                    None => return Err(format!("Invalid range {}, try 10..200", value)),
+                }
+            }
+            "--trace-stack" => match value.parse() {
+                Ok(count) => stack = Some(count),
+                Err(_) => return Err(format!("Invalid stack size {}", value)),
+            },
+            _ => return Err(format!("Unrecognized option {}", option)),
+        }
+    }
+
+    let output: Box<dyn std::io::Write> = match file {
+        Some(file) => match std::fs::File::create(&file) {
+            Ok(file) => Box::new(std::io::BufWriter::new(file)),
+            Err(error) => return Err(format!("Could not create {}: {}", file, error)),
+        },
+        None => Box::new(std::io::stdout()),
+    };
+    let mut trace = vm::trace::new(output);
+    trace.opcodes = opcodes;
+    trace.range = range;
+    if let Some(stack) = stack {
+        trace.stack = stack
+    }
+    Ok(Some(trace))
+}
+
 fn main() {
     let mut nova = core::new();
 
@@ -5,9 +126,12 @@ fn main() {
     nova.add_function("print", native::io::print);
     nova.add_function("println", native::io::println);
     nova.add_function("readln", native::io::readln);
+    nova.signature("print", &[Type::Any]);
+    nova.signature("println", &[Type::Any]);
+    nova.signature("readln", &[]);
 
     // random
-    nova.add_function("random", native::random::random);
+    nova.add_fn("random", native::random::random);
 
     // list
     nova.add_function("length", native::list::length);
@@ -16,28 +140,148 @@ fn main() {
     nova.add_function("last", native::list::last);
     nova.add_function("insert", native::list::insert);
     nova.add_function("remove", native::list::remove);
-    
+    nova.add_function_with_vm("map", native::list::map);
+    nova.add_function_with_vm("filter", native::list::filter);
+    nova.add_function_with_vm("sort_by", native::list::sort_by);
+    nova.add_function_with_vm("each", native::list::each);
+    nova.signature("length", &[Type::List]);
+    nova.signature("push", &[Type::List, Type::Any]);
+    nova.signature("pop", &[Type::List]);
+    nova.signature("last", &[Type::List]);
+    nova.signature("insert", &[Type::List, Type::Int, Type::Any]);
+    nova.signature("remove", &[Type::List, Type::Int]);
+    nova.signature("map", &[Type::List, Type::Callable]);
+    nova.signature("filter", &[Type::List, Type::Callable]);
+    nova.signature("sort_by", &[Type::List, Type::Callable]);
+    nova.signature("each", &[Type::List, Type::Callable]);
+
+    // map
+    nova.add_function("get", native::map::get);
+    nova.add_function("set", native::map::set);
+    nova.add_function("has", native::map::has);
+    nova.add_function("keys", native::map::keys);
+    nova.add_function("values", native::map::values);
+    nova.add_function("delete", native::map::delete);
+    nova.signature("get", &[Type::Map, Type::Any]);
+    nova.signature("set", &[Type::Map, Type::Any, Type::Any]);
+    nova.signature("has", &[Type::Map, Type::Any]);
+    nova.signature("keys", &[Type::Map]);
+    nova.signature("values", &[Type::Map]);
+    nova.signature("delete", &[Type::Map, Type::Any]);
+
     match std::env::args().nth(1) {
         Some(option) => match option.as_str() {
             "run" => {
                 if let Some(filepath) = std::env::args().nth(2) {
+                    let options: Vec<String> = std::env::args().skip(3).collect();
+                    let (limits, options) = match limit_options(options) {
+                        Ok(limits) => limits,
+                        Err(error) => {
+                            println!("Error: {}", error);
+                            return;
+                        }
+                    };
+                    nova.limit(limits);
+                    let (profile, stacks, options) = profile_options(options);
+                    match trace_options(&options) {
+                        Ok(trace) => nova.trace(trace),
+                        Err(error) => {
+                            println!("Error: {}", error);
+                            return;
+                        }
+                    }
+                    nova.profile(profile);
+                    if filepath.ends_with(".nvc") {
+                        nova.run_bytecode(&filepath);
+                    } else {
+                        if let Err(error) = nova.open_file(&filepath) {
+                            println!("{:?}", error);
+                            return;
+                        }
+                        nova.run();
+                    }
+
+                    if let Some(table) = nova.profile_table() {
+                        print!("\n{}", table)
+                    }
+                    if let (Some(stacks), Some(output)) = (nova.profile_stacks(), stacks) {
+                        if let Err(error) = std::fs::write(&output, stacks) {
+                            println!("Error: Could not write {}: {}", output, error)
+                        }
+                    }
+                } else {
+                    println!("Error: No file path specified");
+                }
+            }
+            "build" => {
+                if let Some(filepath) = std::env::args().nth(2) {
+                    let output = match (std::env::args().nth(3).as_deref(), std::env::args().nth(4))
+                    {
+                        (Some("-o"), Some(output)) => output,
+                        (None, _) => match filepath.strip_suffix(".nv") {
+                            Some(stem) => format!("{}.nvc", stem),
+                            None => format!("{}.nvc", filepath),
+                        },
+                        _ => {
+                            println!("Error: Usage is nova build file.nv -o file.nvc");
+                            return;
+                        }
+                    };
                     if let Err(error) = nova.open_file(&filepath) {
                         println!("{:?}", error);
                         return;
                     }
-                    nova.run();
+                    nova.build(&output);
                 } else {
                     println!("Error: No file path specified");
                 }
             }
-            "dis" => {
+            "asm" => {
+                if let Some(filepath) = std::env::args().nth(2) {
+                    let output = match (std::env::args().nth(3).as_deref(), std::env::args().nth(4))
+                    {
+                        (Some("-o"), Some(output)) => output,
+                        (None, _) => match filepath.strip_suffix(".nva") {
+                            Some(stem) => format!("{}.nvc", stem),
+                            None => format!("{}.nvc", filepath),
+                        },
+                        _ => {
+                            println!("Error: Usage is nova asm file.nva -o file.nvc");
+                            return;
+                        }
+                    };
+                    nova.asm(&filepath, &output);
+                } else {
+                    println!("Error: No file path specified");
+                }
+            }
+            "debug" => {
                 if let Some(filepath) = std::env::args().nth(2) {
                     if let Err(error) = nova.open_file(&filepath) {
                         println!("{:?}", error);
                         return;
                     }
+                    println!("Debugging {}, type help for commands", filepath);
+                    nova.debug(Box::new(debugger::new()));
+                } else {
+                    println!("Error: No file path specified");
+                }
+            }
+            "dis" => {
+                let source = std::env::args().skip(2).any(|arg| arg == "--source");
+                let asm = std::env::args().skip(2).any(|arg| arg == "--asm");
+                let filepath = std::env::args().skip(2).find(|arg| !arg.starts_with("--"));
+                if let Some(filepath) = filepath {
+                    if let Err(error) = nova.open_file(&filepath) {
+                        println!("{:?}", error);
+                        return;
+                    }
+                    if asm {
+                        nova.dis_asm();
+                        return;
+                    }
                     println!("Disassembly:");
-                    nova.dis();
+                    nova.dis(source);
                 } else {
                     println!("Error: No file path specified");
                 }
@@ -73,7 +317,7 @@ fn main() {
                     "exit" => std::process::exit(0),
                     _ => {
                         if !input.is_empty() {
-                            match nova.eval(input, true) {
+                            match nova.eval(input) {
                                 Ok(_) => {}
                                 Err(error) => error.show(),
                             }
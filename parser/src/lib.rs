@@ -1,14 +1,203 @@
-use common::tokens::{Operator, Token};
+use common::{
+    error::NovaError,
+    tokens::{Operator, Token},
+};
+
+// what the previous significant token was, used to validate the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Last {
+    Start,
+    Operand,
+    Operator,
+    OpenParen,
+    Comma,
+}
+
+fn operator_symbol(operator: &Operator) -> &'static str {
+    match operator {
+        Operator::Assign => "=",
+        Operator::Add => "+",
+        Operator::Sub | Operator::Neg => "-",
+        Operator::Mul => "*",
+        Operator::Div => "/",
+        Operator::Mod => "%",
+        Operator::Equals => "==",
+        Operator::NotEquals => "!=",
+        Operator::Gtr => ">",
+        Operator::Lss => "<",
+        Operator::Gte => ">=",
+        Operator::Lte => "<=",
+        Operator::Not => "not",
+        Operator::Invert => "!",
+        Operator::And => "&&",
+        Operator::Or => "||",
+        _ => "operator",
+    }
+}
 
 pub struct Parser {
     operator: Vec<Token>,
     output: Vec<Token>,
+    filepath: String,
+    position: (usize, usize),
+    parens: Vec<(usize, usize)>,
+    comma: (usize, usize),
+    last: Last,
+    dangling: Option<(Operator, (usize, usize))>,
+    logical: Vec<usize>,
+    // arguments counted so far for each open parenthesis, None when it
+    // only groups an expression
+    arguments: Vec<Option<usize>>,
 }
 
 impl Parser {
+    pub fn filepath(&mut self, filepath: &str) {
+        self.filepath = filepath.to_string()
+    }
+
+    fn nested(&self) -> Parser {
+        let mut parser = new();
+        parser.filepath = self.filepath.clone();
+        parser
+    }
+
+    fn error(&self, msg: String, note: &str, position: (usize, usize)) -> NovaError {
+        common::error::parser_error(
+            msg,
+            note.to_string(),
+            position.0,
+            position.1,
+            self.filepath.clone(),
+        )
+    }
+
+    fn check_dangling(&mut self) -> Result<(), NovaError> {
+        if let Some((operator, position)) = self.dangling.take() {
+            return Err(self.error(
+                format!(
+                    "Operator {} is missing its right operand",
+                    operator_symbol(&operator)
+                ),
+                "Try adding a value after the operator, or removing it",
+                position,
+            ));
+        }
+        Ok(())
+    }
+
+    // a line or the input may end with a prefix operator that acts on the
+    // stack, but never with a binary one
+    fn check_line_end(&mut self) -> Result<(), NovaError> {
+        if let Some((Operator::Neg | Operator::Not | Operator::Invert, _)) = self.dangling {
+            self.dangling = None;
+        }
+        self.check_dangling()
+    }
+
+    // validates the token stream before shunting, rejecting input that would
+    // otherwise produce a malformed rpn stream
+    fn check(&mut self, token: &Token, next: Option<&Token>) -> Result<(), NovaError> {
+        match token {
+            Token::Position(line, row) => {
+                self.position = (*line, *row);
+                return Ok(());
+            }
+            Token::CurrentFile(file) => {
+                self.filepath = file.clone();
+                return Ok(());
+            }
+            Token::LinePosition(_) => {
+                self.check_line_end()?;
+                self.last = Last::Start;
+            }
+            Token::Symbol('(') => {
+                self.parens.push(self.position);
+                self.dangling = None;
+                self.last = Last::OpenParen;
+            }
+            Token::Symbol(')') => {
+                if self.parens.pop().is_none() {
+                    return Err(self.error(
+                        "Unexpected closing parenthesis".to_string(),
+                        "Try removing it, or adding a matching (",
+                        self.position,
+                    ));
+                }
+                if self.last == Last::Comma {
+                    return Err(self.error(
+                        "Trailing comma before closing parenthesis".to_string(),
+                        "Try removing the comma",
+                        self.comma,
+                    ));
+                }
+                self.check_dangling()?;
+                self.last = Last::Operand;
+            }
+            Token::Symbol(',') => {
+                // commas created by ?{ only flush operators
+                if let Some(Token::ConditionalBlock(_)) = next {
+                    return Ok(());
+                }
+                if matches!(self.last, Last::OpenParen | Last::Comma) {
+                    return Err(self.error(
+                        "Unexpected comma".to_string(),
+                        "Try removing the comma, or adding a value before it",
+                        self.position,
+                    ));
+                }
+                self.check_dangling()?;
+                self.comma = self.position;
+                self.last = Last::Comma;
+            }
+            Token::Op(operator) => match operator {
+                Operator::Neg | Operator::Not | Operator::Invert => {
+                    self.dangling = Some((operator.clone(), self.position));
+                    self.last = Last::Operator;
+                }
+                Operator::Assign
+                | Operator::Add
+                | Operator::Sub
+                | Operator::Mul
+                | Operator::Div
+                | Operator::Mod
+                | Operator::Equals
+                | Operator::NotEquals
+                | Operator::Gtr
+                | Operator::Lss
+                | Operator::Gte
+                | Operator::Lte
+                | Operator::And
+                | Operator::Or => {
+                    if self.last == Last::Operand {
+                        self.dangling = Some((operator.clone(), self.position));
+                    }
+                    self.last = Last::Operator;
+                }
+                _ => {
+                    self.dangling = None;
+                    self.last = Last::Operand;
+                }
+            },
+            _ => {
+                self.dangling = None;
+                self.last = Last::Operand;
+            }
+        }
+        Ok(())
+    }
+
     #[inline(always)]
-    pub fn parse(&mut self, input: Vec<Token>) -> Result<Vec<Token>, common::error::NovaError> {
-        for token in input {
+    pub fn parse(&mut self, input: Vec<Token>) -> Result<Vec<Token>, NovaError> {
+        let mut input = input.into_iter().peekable();
+        while let Some(token) = input.next() {
+            let infix = self.last == Last::Operand;
+            let last = self.last;
+            let starts = self.starts_value(&token, last);
+            self.check(&token, input.peek())?;
+            if starts {
+                self.count_argument();
+                self.end_prefix();
+            }
             match &token {
                 Token::GlobalReg(_) => {
                     self.output.push(token);
@@ -23,19 +212,13 @@ impl Parser {
                     self.output.push(token);
                 }
                 Token::ConditionalBlock(block) => {
-                    let mut parser = new();
-                    let parsed = match parser.parse(block.to_vec()) {
-                        Ok(parsed) => parsed,
-                        Err(error) => return Err(error),
-                    };
+                    let mut parser = self.nested();
+                    let parsed = parser.parse(block.to_vec())?;
                     self.output.push(Token::ConditionalBlock(parsed.to_vec()))
                 }
                 Token::Doblock(block) => {
-                    let mut parser = new();
-                    let parsed = match parser.parse(block.to_vec()) {
-                        Ok(parsed) => parsed,
-                        Err(error) => return Err(error),
-                    };
+                    let mut parser = self.nested();
+                    let parsed = parser.parse(block.to_vec())?;
                     self.output.push(Token::Doblock(parsed.to_vec()))
                 }
                 Token::BlockLiteral(block) => match self.output.last().cloned() {
@@ -43,22 +226,16 @@ impl Parser {
                         self.output.pop();
                         if let Some(Token::Arguments(input2)) = self.output.last().cloned() {
                             self.output.pop();
-                            let mut parser = new();
-                            let parsed = match parser.parse(block.to_vec()) {
-                                Ok(parsed) => parsed,
-                                Err(error) => return Err(error),
-                            };
+                            let mut parser = self.nested();
+                            let parsed = parser.parse(block.to_vec())?;
                             self.output.push(Token::Closure(
                                 input2.to_vec(),
                                 input.to_vec(),
                                 parsed.to_vec(),
                             ))
                         } else {
-                            let mut parser = new();
-                            let parsed = match parser.parse(block.to_vec()) {
-                                Ok(parsed) => parsed,
-                                Err(error) => return Err(error),
-                            };
+                            let mut parser = self.nested();
+                            let parsed = parser.parse(block.to_vec())?;
                             self.output
                                 .push(Token::Function(input.to_vec(), parsed.to_vec()))
                         }
@@ -66,37 +243,44 @@ impl Parser {
                     Some(Token::Bindings(input)) => {
                         self.output.pop();
 
-                        let mut parser = new();
-                        let parsed = match parser.parse(block.to_vec()) {
-                            Ok(parsed) => parsed,
-                            Err(error) => return Err(error),
-                        };
+                        let mut parser = self.nested();
+                        let parsed = parser.parse(block.to_vec())?;
                         self.output
                             .push(Token::LetBinding(input.to_vec(), parsed.to_vec()))
                     }
                     _ => {
-                        let mut parser = new();
-                        let parsed = match parser.parse(block.to_vec()) {
-                            Ok(parsed) => parsed,
-                            Err(error) => return Err(error),
-                        };
+                        let mut parser = self.nested();
+                        let parsed = parser.parse(block.to_vec())?;
                         self.output.push(Token::BlockLiteral(parsed.to_vec()))
                     }
                 },
-                Token::List(block) => {
-                    let mut parser = new();
-                    let mut parsed = match parser.parse(block.to_vec()) {
-                        Ok(parsed) => parsed,
-                        Err(error) => return Err(error),
-                    };
+                Token::List(block, _) => {
+                    let mut parser = self.nested();
+                    // values are counted like the arguments of a call
+                    parser.arguments.push(Some(0));
+                    let mut parsed = parser.parse(block.to_vec())?;
+                    let count = parser.arguments.pop().flatten().unwrap_or_default();
+                    parsed.retain(|x| *x != Token::Symbol(' '));
+                    parsed.retain(|x| *x != Token::Symbol(','));
+                    parsed.retain(|x| !matches!(*x, Token::LinePosition(_)));
+                    self.output.push(Token::List(parsed.to_vec(), count))
+                }
+                Token::Map(block, _) => {
+                    let mut parser = self.nested();
+                    // values are counted like the arguments of a call
+                    parser.arguments.push(Some(0));
+                    let mut parsed = parser.parse(block.to_vec())?;
+                    let count = parser.arguments.pop().flatten().unwrap_or_default();
                     parsed.retain(|x| *x != Token::Symbol(' '));
                     parsed.retain(|x| *x != Token::Symbol(','));
                     parsed.retain(|x| !matches!(*x, Token::LinePosition(_)));
-                    self.output.push(Token::List(parsed.to_vec()))
+                    self.output.push(Token::Map(parsed.to_vec(), count))
                 }
                 Token::LinePosition(_) => {
-                    self.output.push(token);
+                    // operators left from the previous line belong to it in
+                    // the line table, so they go out before the new position
                     self.empty_until_open_paren();
+                    self.output.push(token);
                 }
                 Token::Reg(_)
                 | Token::RegRef(_)
@@ -110,13 +294,17 @@ impl Parser {
                 | Token::String(_) => {
                     self.output.push(token);
                 }
-                Token::Call(_) => {
+                Token::Call(..) => {
                     self.operator.push(token);
                 }
                 Token::Symbol(',') => {
                     self.empty_until_open_paren();
                 }
                 Token::Symbol('(') => {
+                    match self.operator.last() {
+                        Some(Token::Call(..)) => self.arguments.push(Some(0)),
+                        _ => self.arguments.push(None),
+                    }
                     self.operator.push(token);
                 }
                 Token::Symbol(')') => {
@@ -124,12 +312,13 @@ impl Parser {
                         if last == Token::Symbol('(') {
                             break;
                         } else {
-                            self.output.push(last);
+                            self.emit(last);
                         }
                     }
-                    if let Some(Token::Call(_)) = self.operator.last() {
-                        if let Some(last) = self.operator.pop() {
-                            self.output.push(last);
+                    let count = self.arguments.pop().flatten().unwrap_or_default();
+                    if let Some(Token::Call(..)) = self.operator.last() {
+                        if let Some(Token::Call(name, _)) = self.operator.pop() {
+                            self.emit(Token::Call(name, count));
                         }
                     }
                 }
@@ -144,15 +333,18 @@ impl Parser {
                     | Operator::Mod
                     | Operator::And
                     | Operator::Or
+                    | Operator::NotEquals
                     | Operator::Gtr
                     | Operator::Lss
+                    | Operator::Gte
+                    | Operator::Lte
                     | Operator::Invert => {
                         if let Some(temp) = self.operator.last().cloned() {
                             if temp != Token::Symbol('(') {
                                 while let Some(op) = self.operator.last() {
                                     if op.precedence() > token.precedence() {
                                         if let Some(t) = self.operator.pop() {
-                                            self.output.push(t);
+                                            self.emit(t);
                                         }
                                     } else {
                                         break;
@@ -163,7 +355,7 @@ impl Parser {
                                         && token.is_left_associative()
                                     {
                                         if let Some(t) = self.operator.pop() {
-                                            self.output.push(t);
+                                            self.emit(t);
                                         }
                                     } else {
                                         break;
@@ -171,7 +363,17 @@ impl Parser {
                                 }
                             }
                         }
-                        self.operator.push(token);
+                        match token {
+                            // without a left operand && and || act on the stack
+                            Token::Op(Operator::And | Operator::Or) if !infix => {
+                                self.output.push(token)
+                            }
+                            Token::Op(Operator::And | Operator::Or) => {
+                                self.logical.push(self.output.len());
+                                self.operator.push(token)
+                            }
+                            _ => self.operator.push(token),
+                        }
                     }
                     Operator::PopBindings => {
                         self.empty_until_open_paren();
@@ -197,18 +399,83 @@ impl Parser {
             }
         }
 
+        self.check_line_end()?;
+        if let Some(position) = self.parens.last() {
+            return Err(self.error(
+                "Parenthesis left open".to_string(),
+                "Try adding a matching )",
+                *position,
+            ));
+        }
+
         self.emtpy_operators();
 
         Ok(self.output.to_owned())
     }
 
+    // a token starts a new value unless it continues an expression or
+    // belongs to the token before it
+    fn starts_value(&self, token: &Token, last: Last) -> bool {
+        let starts = match token {
+            Token::Position(..)
+            | Token::CurrentFile(_)
+            | Token::LinePosition(_)
+            | Token::Symbol(')' | ',')
+            | Token::ConditionalBlock(_) => false,
+            Token::Symbol('(') => !matches!(self.operator.last(), Some(Token::Call(..))),
+            Token::Op(operator) => {
+                matches!(operator, Operator::Neg | Operator::Not | Operator::Invert)
+            }
+            _ => true,
+        };
+        // the parameters of a function or the bindings of a let come before
+        // their block
+        let body = matches!(
+            self.output.last(),
+            Some(Token::Arguments(_) | Token::Bindings(_))
+        );
+        starts && !body && last != Last::Operator
+    }
+
+    // counts the arguments of the innermost call
+    fn count_argument(&mut self) {
+        if let Some(Some(count)) = self.arguments.last_mut() {
+            *count += 1
+        }
+    }
+
+    // a prefix operator acts on the value after it, which is complete once
+    // the next one starts
+    fn end_prefix(&mut self) {
+        while let Some(Token::Op(Operator::Neg | Operator::Not | Operator::Invert)) =
+            self.operator.last()
+        {
+            if let Some(operator) = self.operator.pop() {
+                self.emit(operator)
+            }
+        }
+    }
+
+    // moves an operator to the output, the right operand of && and || is
+    // wrapped so it can be skipped
+    fn emit(&mut self, token: Token) {
+        match token {
+            Token::Op(operator @ (Operator::And | Operator::Or)) => {
+                let start = self.logical.pop().unwrap_or(self.output.len());
+                let right = self.output.split_off(start);
+                self.output.push(Token::ShortCircuit(operator, right))
+            }
+            _ => self.output.push(token),
+        }
+    }
+
     fn empty_until_open_paren(&mut self) {
         while let Some(last) = self.operator.last() {
             match last {
                 Token::Symbol('(') => break,
                 _ => {
                     if let Some(tok) = self.operator.pop() {
-                        self.output.push(tok)
+                        self.emit(tok)
                     }
                 }
             }
@@ -217,7 +484,7 @@ impl Parser {
 
     fn emtpy_operators(&mut self) {
         while let Some(t) = self.operator.pop() {
-            self.output.push(t);
+            self.emit(t);
         }
     }
 }
@@ -226,5 +493,13 @@ pub fn new() -> Parser {
     Parser {
         operator: vec![],
         output: vec![],
+        filepath: String::new(),
+        position: (0, 0),
+        parens: vec![],
+        comma: (0, 0),
+        last: Last::Start,
+        dangling: None,
+        logical: vec![],
+        arguments: vec![],
     }
 }
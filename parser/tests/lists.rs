@@ -0,0 +1,42 @@
+use common::tokens::{Operator, Token};
+
+fn parse(input: &str) -> Vec<Token> {
+    let mut lexer = lexer::new();
+    lexer.insert_string(input);
+    let tokens = lexer.parse().unwrap().to_vec();
+    parser::new().parse(tokens).unwrap()
+}
+
+// the list literal of a program made of one
+fn list(input: &str) -> (Vec<Token>, usize) {
+    match parse(input)
+        .into_iter()
+        .find(|token| matches!(token, Token::List(..)))
+    {
+        Some(Token::List(items, count)) => (items, count),
+        _ => panic!("no list in {}", input),
+    }
+}
+
+#[test]
+fn prefix_operators_take_the_value_after_them() {
+    let invert = Token::Op(Operator::Invert);
+    let neg = Token::Op(Operator::Neg);
+    let (yes, no) = (Token::Reg("true".into()), Token::Reg("false".into()));
+    assert_eq!(
+        list("[!true false]"),
+        (vec![yes.clone(), invert.clone(), no.clone()], 2)
+    );
+    assert_eq!(list("[true !false]"), (vec![yes, no, invert], 2));
+    assert_eq!(
+        list("[-1 2]"),
+        (vec![Token::Integer(1), neg, Token::Integer(2)], 2)
+    );
+}
+
+#[test]
+fn values_are_counted() {
+    assert_eq!(list("[1 + 2, 3]").1, 2);
+    assert_eq!(list("[[1 2] 3]").1, 2);
+    assert_eq!(list("[]").1, 0);
+}
@@ -0,0 +1,351 @@
+use crate::state::{self, MapKey, UserData, VmBig};
+use common::{error::NovaError, signature::Type};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    rc::Rc,
+};
+
+/// A value of the wrong type, `NativeFn` turns it into an argument error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub expected: String,
+    pub found: &'static str,
+}
+
+fn mismatch<T>(expected: &str, value: &VmBig) -> Result<T, Mismatch> {
+    Err(Mismatch {
+        expected: expected.to_string(),
+        found: value.type_name(),
+    })
+}
+
+/// Rust values a native can take as an argument.
+pub trait FromNova: Sized {
+    /// The parameter type declared for natives taking this.
+    fn param() -> Type;
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch>;
+}
+
+/// Rust values a native can return.
+pub trait IntoNova {
+    fn into_nova(self) -> VmBig;
+}
+
+impl FromNova for VmBig {
+    fn param() -> Type {
+        Type::Any
+    }
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+        Ok(value)
+    }
+}
+
+impl IntoNova for VmBig {
+    fn into_nova(self) -> VmBig {
+        self
+    }
+}
+
+macro_rules! scalar {
+    ($rust:ty, $variant:ident, $name:literal) => {
+        impl FromNova for $rust {
+            fn param() -> Type {
+                Type::$variant
+            }
+            fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+                match value {
+                    VmBig::$variant(value) => Ok(value),
+                    value => mismatch($name, &value),
+                }
+            }
+        }
+
+        impl IntoNova for $rust {
+            fn into_nova(self) -> VmBig {
+                VmBig::$variant(self)
+            }
+        }
+    };
+}
+
+scalar!(i64, Int, "Int");
+scalar!(f64, Float, "Float");
+scalar!(bool, Bool, "Bool");
+scalar!(char, Char, "Char");
+
+impl FromNova for String {
+    fn param() -> Type {
+        Type::String
+    }
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+        match value {
+            VmBig::String(string) => Ok(Rc::unwrap_or_clone(string)),
+            value => mismatch("String", &value),
+        }
+    }
+}
+
+impl IntoNova for String {
+    fn into_nova(self) -> VmBig {
+        VmBig::String(Rc::new(self))
+    }
+}
+
+impl IntoNova for &str {
+    fn into_nova(self) -> VmBig {
+        VmBig::String(Rc::new(self.to_string()))
+    }
+}
+
+impl<T: FromNova> FromNova for Vec<T> {
+    fn param() -> Type {
+        Type::List
+    }
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+        match value {
+            VmBig::List(list) => Rc::unwrap_or_clone(list)
+                .into_iter()
+                .map(T::from_nova)
+                .collect(),
+            value => mismatch("List", &value),
+        }
+    }
+}
+
+impl<T: IntoNova> IntoNova for Vec<T> {
+    fn into_nova(self) -> VmBig {
+        VmBig::List(Rc::new(self.into_iter().map(T::into_nova).collect()))
+    }
+}
+
+impl FromNova for UserData {
+    fn param() -> Type {
+        Type::UserData
+    }
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+        match value {
+            VmBig::UserData(data) => Ok(data),
+            value => mismatch("UserData", &value),
+        }
+    }
+}
+
+impl IntoNova for UserData {
+    fn into_nova(self) -> VmBig {
+        VmBig::UserData(self)
+    }
+}
+
+/// None stands for Nova's None, any other value is converted as `T`.
+impl<T: FromNova> FromNova for Option<T> {
+    fn param() -> Type {
+        Type::Any
+    }
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+        match value {
+            VmBig::None => Ok(None),
+            value => T::from_nova(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoNova> IntoNova for Option<T> {
+    fn into_nova(self) -> VmBig {
+        match self {
+            Some(value) => value.into_nova(),
+            None => VmBig::None,
+        }
+    }
+}
+
+// tuples are lists with exactly one item per field
+macro_rules! tuple {
+    ($length:literal, $($field:ident),+) => {
+        impl<$($field: FromNova),+> FromNova for ($($field,)+) {
+            fn param() -> Type {
+                Type::List
+            }
+            fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+                let expected = concat!("List of ", $length, " items");
+                match value {
+                    VmBig::List(list) if list.len() == $length => {
+                        let mut items = Rc::unwrap_or_clone(list).into_iter();
+                        Ok(($($field::from_nova(items.next().unwrap_or(VmBig::None))?,)+))
+                    }
+                    value => mismatch(expected, &value),
+                }
+            }
+        }
+
+        impl<$($field: IntoNova),+> IntoNova for ($($field,)+) {
+            #[allow(non_snake_case)]
+            fn into_nova(self) -> VmBig {
+                let ($($field,)+) = self;
+                VmBig::List(Rc::new(vec![$($field.into_nova()),+]))
+            }
+        }
+    };
+}
+
+tuple!(2, A, B);
+tuple!(3, A, B, C);
+tuple!(4, A, B, C, D);
+
+impl From<bool> for MapKey {
+    fn from(key: bool) -> MapKey {
+        MapKey::Bool(key)
+    }
+}
+
+impl From<i64> for MapKey {
+    fn from(key: i64) -> MapKey {
+        MapKey::Int(key)
+    }
+}
+
+impl From<char> for MapKey {
+    fn from(key: char) -> MapKey {
+        MapKey::Char(key)
+    }
+}
+
+impl From<String> for MapKey {
+    fn from(key: String) -> MapKey {
+        MapKey::String(key)
+    }
+}
+
+impl<K: FromNova + Ord, V: FromNova> FromNova for BTreeMap<K, V> {
+    fn param() -> Type {
+        Type::Map
+    }
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+        match value {
+            VmBig::Map(map) => Rc::unwrap_or_clone(map)
+                .into_iter()
+                .map(|(key, value)| Ok((K::from_nova(key.to_value())?, V::from_nova(value)?)))
+                .collect(),
+            value => mismatch("Map", &value),
+        }
+    }
+}
+
+impl<K: Into<MapKey>, V: IntoNova> IntoNova for BTreeMap<K, V> {
+    fn into_nova(self) -> VmBig {
+        VmBig::Map(Rc::new(
+            self.into_iter()
+                .map(|(key, value)| (key.into(), value.into_nova()))
+                .collect(),
+        ))
+    }
+}
+
+impl<K: FromNova + Eq + Hash, V: FromNova> FromNova for HashMap<K, V> {
+    fn param() -> Type {
+        Type::Map
+    }
+    fn from_nova(value: VmBig) -> Result<Self, Mismatch> {
+        match value {
+            VmBig::Map(map) => Rc::unwrap_or_clone(map)
+                .into_iter()
+                .map(|(key, value)| Ok((K::from_nova(key.to_value())?, V::from_nova(value)?)))
+                .collect(),
+            value => mismatch("Map", &value),
+        }
+    }
+}
+
+impl<K: Into<MapKey>, V: IntoNova> IntoNova for HashMap<K, V> {
+    fn into_nova(self) -> VmBig {
+        VmBig::Map(Rc::new(
+            self.into_iter()
+                .map(|(key, value)| (key.into(), value.into_nova()))
+                .collect(),
+        ))
+    }
+}
+
+/// What a native written as a Rust function can return. Values are pushed
+/// as their Nova form, `()` pushes nothing and errors stop the program.
+pub trait NativeResult {
+    fn push(self, state: &mut state::State) -> Result<(), NovaError>;
+}
+
+impl<T: IntoNova> NativeResult for T {
+    fn push(self, state: &mut state::State) -> Result<(), NovaError> {
+        let value = self.into_nova();
+        let bytes = state::size(&value);
+        state.push(value);
+        state.allocate(bytes)
+    }
+}
+
+impl NativeResult for () {
+    fn push(self, _: &mut state::State) -> Result<(), NovaError> {
+        Ok(())
+    }
+}
+
+impl<T: NativeResult, E: Into<NovaError>> NativeResult for Result<T, E> {
+    fn push(self, state: &mut state::State) -> Result<(), NovaError> {
+        match self {
+            Ok(value) => value.push(state),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+/// Rust functions of up to four `FromNova` arguments returning a
+/// `NativeResult`, see `Vm::add_native_fn`.
+pub trait NativeFn<Args> {
+    fn params() -> Vec<Type>;
+    fn call(&self, name: &str, state: &mut state::State) -> Result<(), NovaError>;
+}
+
+fn argument<T: FromNova>(name: &str, position: usize, value: VmBig) -> Result<T, NovaError> {
+    T::from_nova(value).map_err(|mismatch| {
+        common::error::runetime_error(format!(
+            "expected {}, got {} in argument {} of {}",
+            mismatch.expected, mismatch.found, position, name
+        ))
+    })
+}
+
+macro_rules! native_fn {
+    ($($arg:ident $position:literal),*) => {
+        impl<F, R, $($arg: FromNova),*> NativeFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R,
+            R: NativeResult,
+        {
+            fn params() -> Vec<Type> {
+                vec![$($arg::param()),*]
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call(&self, name: &str, state: &mut state::State) -> Result<(), NovaError> {
+                let count = <[usize]>::len(&[$($position),*]);
+                let mut values: Vec<VmBig> = (0..count)
+                    .map(|_| state.pop().unwrap_or(VmBig::None))
+                    .collect();
+                values.reverse();
+                let mut values = values.into_iter();
+                $(
+                    let $arg = argument::<$arg>(
+                        name,
+                        $position,
+                        values.next().unwrap_or(VmBig::None),
+                    )?;
+                )*
+                self($($arg),*).push(state)
+            }
+        }
+    };
+}
+
+native_fn!();
+native_fn!(A 1);
+native_fn!(A 1, B 2);
+native_fn!(A 1, B 2, C 3);
+native_fn!(A 1, B 2, C 3, D 4);
@@ -0,0 +1,120 @@
+use crate::Vm;
+use common::error::NovaError;
+
+/// Called by `Vm::run` before every instruction once installed with
+/// `Vm::hook`. Returning an error stops the program with that error.
+pub trait Hook {
+    fn before(&mut self, vm: &Vm) -> Result<(), NovaError>;
+}
+
+pub fn new() -> Debugger {
+    Debugger {
+        breakpoints: vec![],
+        step: Step::Into,
+        last: None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// A source line, in any file when no file is given. Files match when
+    /// the path the code was compiled from ends with them.
+    Line(Option<String>, usize),
+    /// The start of the block, function or closure bound to this name.
+    Function(String),
+}
+
+/// How far to run before stopping again. `Over` does not stop inside the
+/// functions and closures called from the current line, `Out` runs until
+/// the innermost call frame, including block, loop and for frames, returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Continue,
+    Into,
+    Over(usize),
+    Out(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Breakpoint(usize),
+}
+
+/// Decides where a program being debugged stops. A front end calls `check`
+/// from its `Hook` and, when it returns a stop, inspects the VM and calls
+/// `resume` with the user's next step. A new debugger stops on the first
+/// line.
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    step: Step,
+    last: Option<(usize, String)>,
+}
+
+impl Debugger {
+    pub fn check(&mut self, vm: &Vm) -> Option<Stop> {
+        let offset = vm.current_instruction();
+        let here = vm
+            .debug_info()
+            .line_at(offset)
+            .map(|(line, file)| (line, file.to_string()));
+        let new_line = here.is_some() && here != self.last;
+
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .position(|breakpoint| match breakpoint {
+                Breakpoint::Line(file, line) => match &here {
+                    Some((here_line, here_file)) => {
+                        new_line
+                            && here_line == line
+                            && file
+                                .as_ref()
+                                .is_none_or(|file| here_file.ends_with(file.as_str()))
+                    }
+                    None => false,
+                },
+                Breakpoint::Function(name) => vm
+                    .debug_info()
+                    .chunks
+                    .iter()
+                    .any(|chunk| chunk.start == offset && chunk.name.as_ref() == Some(name)),
+            });
+        let stop = match breakpoint {
+            Some(index) => Some(Stop::Breakpoint(index)),
+            None => match self.step {
+                Step::Continue => None,
+                Step::Into => new_line.then_some(Stop::Step),
+                Step::Over(depth) => (new_line && vm.call_depth() <= depth).then_some(Stop::Step),
+                Step::Out(depth) => (vm.frame_depth() < depth).then_some(Stop::Step),
+            },
+        };
+
+        // lines run inside a call being stepped over do not count as left
+        match self.step {
+            Step::Over(depth) if vm.call_depth() > depth => {}
+            _ => {
+                if here.is_some() {
+                    self.last = here
+                }
+            }
+        }
+        stop
+    }
+
+    pub fn resume(&mut self, step: Step) {
+        self.step = step
+    }
+}
+
+impl Step {
+    /// Stepping over the line `vm` is stopped on.
+    pub fn over(vm: &Vm) -> Step {
+        Step::Over(vm.call_depth())
+    }
+
+    /// Stepping out of the frame `vm` is stopped in.
+    pub fn out(vm: &Vm) -> Step {
+        Step::Out(vm.frame_depth())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::state::LargeList;
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub enum CallType {
@@ -6,7 +7,7 @@ pub enum CallType {
     Block,
     Function,
     Closure,
-    For(usize, LargeList, usize),
+    For(usize, Rc<LargeList>, usize),
 }
 
 #[derive(Debug, Clone)]
@@ -1,34 +1,143 @@
-//use std::time::Instant;
+use std::time::Instant;
 pub type CallBack = fn(state: &mut state::State) -> Result<(), NovaError>;
+/// A native that can call back into Nova code with `Vm::call`.
+pub type VmCallBack = fn(vm: &mut Vm) -> Result<(), NovaError>;
+/// A native that keeps state of its own between calls.
+pub type ClosureCallBack = Rc<RefCell<dyn FnMut(&mut state::State) -> Result<(), NovaError>>>;
 
-use common::{code::Code, error::NovaError};
-//use modulo::Mod;
+/// A registered native. Plain function pointers are called directly,
+/// closures go through their RefCell.
+#[derive(Clone)]
+pub enum Native {
+    State(CallBack),
+    Vm(VmCallBack),
+    Closure(ClosureCallBack),
+}
+
+use common::{
+    code::Code,
+    container::Container,
+    debug::{DebugInfo, Symbol, SymbolKind},
+    error::{NovaError, TraceFrame},
+    signature::{Signature, Type},
+};
+pub mod convert;
+pub mod debugger;
 mod frame;
+pub mod profile;
 pub mod state;
-use fxhash::FxHashMap;
+pub mod trace;
 use state::{VmBig, VmSmall};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    rc::{Rc, Weak},
+    sync::{atomic::AtomicBool, Arc},
+};
 
 use crate::frame::CallType;
+
+// a register value lent to a native, weak for shared values so the native
+// can be their only owner
+enum Lent {
+    List(Weak<state::LargeList>),
+    Map(Weak<state::Map>),
+    String(Weak<String>),
+    Other(VmBig),
+}
+
+impl Lent {
+    fn new(value: VmBig) -> Lent {
+        match value {
+            VmBig::List(list) => Lent::List(Rc::downgrade(&list)),
+            VmBig::Map(map) => Lent::Map(Rc::downgrade(&map)),
+            VmBig::String(string) => Lent::String(Rc::downgrade(&string)),
+            value => Lent::Other(value),
+        }
+    }
+
+    // the value again if the native did not let go of it
+    fn back(self) -> Option<VmBig> {
+        match self {
+            Lent::List(list) => list.upgrade().map(VmBig::List),
+            Lent::Map(map) => map.upgrade().map(VmBig::Map),
+            Lent::String(string) => string.upgrade().map(VmBig::String),
+            Lent::Other(value) => Some(value),
+        }
+    }
+}
+
+fn operand_error(opcode: &str, expected: &str, found: String) -> NovaError {
+    common::error::runetime_error(format!("{} expected {}, found {}", opcode, expected, found))
+}
+
+fn overflow(left: i64, symbol: &str, right: i64) -> NovaError {
+    common::error::runetime_error(format!("Integer overflow: {} {} {}", left, symbol, right))
+}
+
+// remainder taking the sign of a positive divisor, None on overflow
+fn modulo(left: i64, right: i64) -> Option<i64> {
+    match left.checked_rem(right)? {
+        result if result < 0 => result.checked_add(right),
+        result => Some(result),
+    }
+}
+
 #[allow(dead_code)]
 pub struct Vm {
     program: Vec<u8>,
-    pub native_functions: Vec<CallBack>,
+    debug: DebugInfo,
+    pub native_functions: Vec<Native>,
+    native_names: Vec<String>,
+    // checked before the native at the same index runs
+    signatures: Vec<Option<Signature>>,
     current_instruction: usize,
+    instruction_start: usize,
     callstack: Vec<frame::Frame>,
+    // stack height when the last appended code started, and whether it
+    // stopped on an error
+    base: usize,
+    failed: bool,
+    // register the next native's result is assigned to, see TAKEID
+    take: Option<usize>,
+    // call frames below this belong to a caller of `call`
+    floor: usize,
     state: state::State,
     dispatch: usize,
-    analizer: FxHashMap<u8, std::time::Duration>,
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    interrupt: Arc<AtomicBool>,
+    max_depth: Option<usize>,
+    max_stack: Option<usize>,
+    trace: Option<trace::Trace>,
+    hook: Option<Box<dyn debugger::Hook>>,
+    analizer: Option<profile::Profile>,
 }
 
 pub fn new() -> Vm {
     Vm {
         program: vec![],
+        debug: common::debug::new(),
         current_instruction: 0,
+        instruction_start: 0,
         state: state::new(),
         callstack: vec![],
+        base: 0,
+        failed: false,
+        take: None,
+        floor: 0,
         dispatch: 0,
-        analizer: FxHashMap::default(),
+        fuel: None,
+        deadline: None,
+        interrupt: Arc::new(AtomicBool::new(false)),
+        max_depth: None,
+        max_stack: None,
+        trace: None,
+        hook: None,
+        analizer: None,
         native_functions: vec![],
+        native_names: vec![],
+        signatures: vec![],
     }
 }
 
@@ -37,25 +146,560 @@ impl Vm {
         self.program = program
     }
 
-    fn next(&mut self) -> u8 {
-        //self.dispatch += 1;
-        let result = &self.program[self.current_instruction];
+    fn next(&mut self) -> Result<u8, NovaError> {
+        match self.byte() {
+            Some(byte) => Ok(byte),
+            None => Err(self.malformed()),
+        }
+    }
+
+    // operand decoders, see the encoding notes in common::code
+    #[inline(always)]
+    fn byte(&mut self) -> Option<u8> {
+        let byte = self.program.get(self.current_instruction).copied();
         self.current_instruction += 1;
-        *result
+        byte
+    }
+
+    #[inline(always)]
+    fn uint(&mut self) -> Result<usize, NovaError> {
+        match common::code::read_uint(|| self.byte()) {
+            Some(value) => Ok(value),
+            None => Err(self.malformed()),
+        }
+    }
+
+    #[inline(always)]
+    fn int(&mut self) -> Result<i64, NovaError> {
+        match common::code::read_int(|| self.byte()) {
+            Some(value) => Ok(value),
+            None => Err(self.malformed()),
+        }
+    }
+
+    #[inline(always)]
+    fn float(&mut self) -> Result<f64, NovaError> {
+        match common::code::read_float(|| self.byte()) {
+            Some(value) => Ok(value),
+            None => Err(self.malformed()),
+        }
+    }
+
+    #[inline(always)]
+    fn char(&mut self) -> Result<char, NovaError> {
+        match common::code::read_char(|| self.byte()) {
+            Some(value) => Ok(value),
+            None => Err(self.malformed()),
+        }
+    }
+
+    // jumps may land at the very end of the program, never past it
+    fn jump(&mut self, distance: usize) -> Result<(), NovaError> {
+        match self.current_instruction.checked_add(distance) {
+            Some(target) if target <= self.program.len() => {
+                self.current_instruction = target;
+                Ok(())
+            }
+            _ => Err(self.malformed()),
+        }
+    }
+
+    fn malformed(&self) -> NovaError {
+        common::error::runetime_error(format!(
+            "Malformed operand for instruction at {}",
+            self.instruction_start
+        ))
+    }
+
+    pub fn debug(&mut self, debug: DebugInfo) {
+        self.debug = debug
+    }
+
+    /// Lets `run` execute at most `fuel` more instructions, None removes the
+    /// limit. Running out stops it with an OutOfFuel error before the next
+    /// instruction, running again with more fuel carries on from there.
+    pub fn fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Stops `run` with a Timeout error once `deadline` has passed, None
+    /// removes it. Like fuel, a later deadline lets the program carry on.
+    pub fn deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline
+    }
+
+    /// A flag any thread can set to stop `run` with an Interrupted error.
+    /// It is cleared when the error is raised, so the program can be resumed.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.interrupt.clone()
+    }
+
+    /// Stops `run` with a stack overflow error once more than `depth` call
+    /// frames are in progress, counting blocks, loops and for bodies.
+    pub fn max_call_depth(&mut self, depth: Option<usize>) {
+        self.max_depth = depth
+    }
+
+    /// Stops `run` with a stack overflow error once the operand stack holds
+    /// more than `size` values.
+    pub fn max_stack(&mut self, size: Option<usize>) {
+        self.max_stack = size
+    }
+
+    /// Fails allocations with an out of memory error once the lists, maps,
+    /// strings and closures the program holds would take more than `bytes`.
+    pub fn max_heap(&mut self, bytes: Option<usize>) {
+        self.state.limit_heap(bytes)
+    }
+
+    /// Instructions executed since the VM was created or reset.
+    pub fn instructions(&self) -> usize {
+        self.dispatch
+    }
+
+    /// Drops the call frames, stack, registers, globals and bindings left
+    /// behind by a run and moves execution back to the start of the program.
+    /// The program, natives, debug info, limits and context are kept.
+    pub fn reset(&mut self) {
+        self.callstack.clear();
+        self.floor = 0;
+        self.state.reset();
+        self.current_instruction = 0;
+        self.instruction_start = 0;
+        self.dispatch = 0;
+    }
+
+    // the deadline and interrupt flag are only polled every 1024 instructions
+    #[inline(always)]
+    fn check_limits(&mut self) -> Result<(), NovaError> {
+        if self.dispatch.is_multiple_of(1024) {
+            if self.interrupt.load(std::sync::atomic::Ordering::Relaxed) {
+                self.interrupt
+                    .store(false, std::sync::atomic::Ordering::Relaxed);
+                return Err(common::error::interrupt_error(format!(
+                    "Stopped after {} instructions",
+                    self.dispatch
+                )));
+            }
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Err(common::error::timeout_error(format!(
+                    "Deadline passed after {} instructions",
+                    self.dispatch
+                )));
+            }
+        }
+        if let Some(depth) = self.max_depth {
+            if self.callstack.len() > depth {
+                return Err(common::error::runetime_error(format!(
+                    "Stack overflow: more than {} calls in progress",
+                    depth
+                )));
+            }
+        }
+        if let Some(size) = self.max_stack {
+            if self.state.stack_len() > size {
+                return Err(common::error::runetime_error(format!(
+                    "Stack overflow: more than {} values on the stack",
+                    size
+                )));
+            }
+        }
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                return Err(common::error::fuel_error(format!(
+                    "Ran out of fuel after {} instructions",
+                    self.dispatch
+                )));
+            }
+            *fuel -= 1;
+        }
+        self.dispatch += 1;
+        Ok(())
+    }
+
+    /// Logs every instruction `run` executes to `trace`, None turns it off.
+    pub fn trace(&mut self, trace: Option<trace::Trace>) {
+        self.trace = trace
+    }
+
+    /// Calls `hook` before every instruction `run` executes, None removes it.
+    pub fn hook(&mut self, hook: Option<Box<dyn debugger::Hook>>) {
+        self.hook = hook
+    }
+
+    fn run_hook(&mut self) -> Result<(), NovaError> {
+        if let Some(mut hook) = self.hook.take() {
+            let result = hook.before(self);
+            self.hook = Some(hook);
+            result?
+        }
+        Ok(())
+    }
+
+    /// Offset of the instruction about to run.
+    pub fn current_instruction(&self) -> usize {
+        self.current_instruction
+    }
+
+    pub fn debug_info(&self) -> &DebugInfo {
+        &self.debug
+    }
+
+    /// The name behind a register, global, upvalue or binding index as seen
+    /// by the instruction running, when the program was compiled with one.
+    pub fn symbol(&self, kind: SymbolKind, index: usize) -> Option<&Symbol> {
+        self.debug.symbol(kind, index, self.instruction_start)
+    }
+
+    // names a register in error messages
+    fn register_name(&self, index: usize) -> String {
+        match self.symbol(SymbolKind::Register, index) {
+            Some(symbol) => symbol.name.clone(),
+            None => format!("register {}", index),
+        }
+    }
+
+    pub fn state(&self) -> &state::State {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut state::State {
+        &mut self.state
+    }
+
+    /// Calls a function, closure or block with `arguments` and returns the
+    /// value it leaves, None when it leaves nothing. The callee runs to
+    /// completion in a nested dispatch loop, so natives added with
+    /// `add_native_with_vm` can use it and calls can nest. A run stopped by
+    /// a limit inside such a call can be reset but not resumed.
+    pub fn call(&mut self, callee: VmBig, arguments: Vec<VmBig>) -> Result<VmBig, NovaError> {
+        let (kind, target) = match &callee {
+            VmBig::Function(target) => (CallType::Function, *target),
+            VmBig::Closure(target, _) => (CallType::Closure, *target),
+            VmBig::Block(target) => (CallType::Block, *target),
+            a => {
+                return Err(operand_error(
+                    "Call",
+                    "Function, Block or Closure",
+                    format!("{:?}", a),
+                ))
+            }
+        };
+        let height = self.state.stack_len();
+        for argument in arguments {
+            self.state.push(argument)
+        }
+        if let VmBig::Closure(_, upvalues) = callee {
+            self.state.allocate_upvalue(upvalues)
+        }
+        let (floor, resume, start) = (self.floor, self.current_instruction, self.instruction_start);
+        self.callstack.push(frame::Frame {
+            kind,
+            target,
+            ret: resume,
+        });
+        self.floor = self.callstack.len();
+        self.goto(target);
+        let result = self.execute();
+        self.floor = floor;
+        result?;
+        self.instruction_start = start;
+        match self.state.stack_len() > height {
+            true => Ok(self.state.pop().unwrap_or(VmBig::None)),
+            false => Ok(VmBig::None),
+        }
+    }
+
+    /// Function and closure calls in progress.
+    pub fn call_depth(&self) -> usize {
+        self.callstack
+            .iter()
+            .filter(|frame| matches!(frame.kind, CallType::Function | CallType::Closure))
+            .count()
+    }
+
+    /// Call frames in progress, counting blocks, loops and for bodies.
+    pub fn frame_depth(&self) -> usize {
+        self.callstack.len()
+    }
+
+    /// Profiles every `run` from now on when `enabled`, starting afresh.
+    pub fn profile(&mut self, enabled: bool) {
+        self.analizer = match enabled {
+            true => Some(profile::new()),
+            false => None,
+        }
+    }
+
+    /// Opcode and function tables of the profile gathered so far.
+    pub fn profile_table(&self) -> Option<String> {
+        Some(self.analizer.as_ref()?.table(&self.debug))
+    }
+
+    /// The profile gathered so far as collapsed stacks for flamegraph tools.
+    pub fn profile_stacks(&self) -> Option<String> {
+        Some(self.analizer.as_ref()?.collapsed(&self.debug))
+    }
+
+    fn trace_instruction(&mut self) -> Result<(), NovaError> {
+        let offset = self.current_instruction;
+        if let (Some(trace), Some(&opcode)) = (&mut self.trace, self.program.get(offset)) {
+            if trace.wants(offset, opcode) {
+                let stack = self.state.top(trace.stack);
+                trace.record(
+                    &self.program,
+                    offset,
+                    self.callstack.len(),
+                    &self.native_names,
+                    stack,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers a native function under the name compiled code refers to it
+    /// by, replacing an earlier one with the same name.
+    pub fn add_native(&mut self, name: &str, function: CallBack) {
+        self.register_native(name, Native::State(function))
+    }
+
+    /// Registers a native that is handed the whole VM, so it can call the
+    /// functions, closures and blocks it is passed.
+    pub fn add_native_with_vm(&mut self, name: &str, function: VmCallBack) {
+        self.register_native(name, Native::Vm(function))
+    }
+
+    /// Registers a closure as a native, for natives that need to own or
+    /// share Rust values such as a connection or a counter.
+    pub fn add_native_closure(
+        &mut self,
+        name: &str,
+        function: impl FnMut(&mut state::State) -> Result<(), NovaError> + 'static,
+    ) {
+        self.register_native(name, Native::Closure(Rc::new(RefCell::new(function))))
+    }
+
+    /// Registers an ordinary Rust function as a native. Its arguments are
+    /// popped and converted with `FromNova`, its result pushed with
+    /// `IntoNova`, and its signature is derived from the parameter types.
+    pub fn add_native_fn<F, Args>(&mut self, name: &str, function: F)
+    where
+        F: convert::NativeFn<Args> + 'static,
+    {
+        let native = name.to_string();
+        self.add_native_closure(name, move |state| function.call(&native, state));
+        self.signature(name, common::signature::new(&F::params()))
+    }
+
+    fn register_native(&mut self, name: &str, function: Native) {
+        match self.native_names.iter().position(|native| native == name) {
+            Some(index) => self.native_functions[index] = function,
+            None => {
+                self.native_names.push(name.to_string());
+                self.native_functions.push(function);
+                self.signatures.push(None)
+            }
+        }
+    }
+
+    /// Gives a registered native a signature, its arguments are checked
+    /// against it before each call. Names that are not registered are left
+    /// alone.
+    pub fn signature(&mut self, name: &str, signature: Signature) {
+        if let Some(index) = self.native_names.iter().position(|native| native == name) {
+            self.signatures[index] = Some(signature)
+        }
+    }
+
+    fn check_arguments(&self, index: usize) -> Result<(), NovaError> {
+        let Some(Some(signature)) = self.signatures.get(index) else {
+            return Ok(());
+        };
+        let name = self
+            .native_names
+            .get(index)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let Some(arguments) = self.state.peek_fast(signature.arity()) else {
+            return Err(common::error::runetime_error(format!(
+                "{} takes {}, got {}",
+                name,
+                signature.arguments(),
+                self.state.stack_len()
+            )));
+        };
+        for (position, (param, argument)) in signature.params.iter().zip(arguments).enumerate() {
+            let matches = match param {
+                Type::Any => true,
+                Type::Int => matches!(argument, VmSmall::Int(_)),
+                Type::Float => matches!(argument, VmSmall::Float(_)),
+                Type::Bool => matches!(argument, VmSmall::Bool(_)),
+                Type::Char => matches!(argument, VmSmall::Char(_)),
+                Type::String => matches!(argument, VmSmall::String),
+                Type::List => matches!(argument, VmSmall::List),
+                Type::Map => matches!(argument, VmSmall::Map),
+                Type::UserData => matches!(argument, VmSmall::UserData),
+                Type::Callable => matches!(
+                    argument,
+                    VmSmall::Function(_) | VmSmall::Closure | VmSmall::Block(_)
+                ),
+            };
+            if !matches {
+                return Err(common::error::runetime_error(format!(
+                    "expected {}, got {} in argument {} of {}",
+                    param.name(),
+                    argument.type_name(),
+                    position + 1,
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads a program from the bytes of a `.nvc` file. The native functions
+    /// it uses are looked up by name among the registered ones and reordered
+    /// to match the indices in its code.
+    pub fn load(&mut self, bytes: &[u8]) -> Result<(), NovaError> {
+        let container = Container::decode(bytes)?;
+        let mut natives = vec![];
+        let mut signatures = vec![];
+        for name in container.natives.iter() {
+            match self.native_names.iter().position(|native| native == name) {
+                Some(index) => {
+                    natives.push(self.native_functions[index].clone());
+                    signatures.push(self.signatures[index].clone())
+                }
+                None => {
+                    return Err(common::error::file_error(format!(
+                        "Bytecode uses native function {}, which is not registered",
+                        name
+                    )))
+                }
+            }
+        }
+        self.native_functions = natives;
+        self.signatures = signatures;
+        self.native_names = container.natives;
+        self.program = container.code;
+        self.debug = container.debug;
+        Ok(())
+    }
+
+    /// Appends code compiled against the current program and moves execution
+    /// to its start, registers, globals and the stack are left as they are.
+    /// Any call interrupted by an error is dropped first, along with what the
+    /// code that failed left on the stack.
+    pub fn append(&mut self, program: Vec<u8>, debug: DebugInfo) {
+        self.callstack.clear();
+        let height = match self.failed {
+            true => self.base,
+            false => self.state.stack_len(),
+        };
+        self.state.unwind(height);
+        self.base = self.state.stack_len();
+        self.failed = false;
+        self.current_instruction = self.program.len();
+        self.program.extend_from_slice(&program);
+        self.debug.append(debug, 0);
     }
 
     pub fn goto(&mut self, addr: usize) {
         self.current_instruction = addr;
     }
 
-    pub fn run(&mut self) -> Result<(), common::error::NovaError> {
+    fn trace_frame(&self, addr: usize) -> Option<TraceFrame> {
+        let (line, file) = self.debug.line_at(addr)?;
+        let function = match self.debug.chunk_at(addr) {
+            Some(chunk) => self.debug.label(chunk),
+            None => "<main>".to_string(),
+        };
+        Some(TraceFrame {
+            function,
+            file: file.to_string(),
+            line,
+        })
+    }
+
+    /// Nova level backtrace of the current instruction and every call frame
+    /// below it, innermost first.
+    pub fn backtrace(&self) -> Vec<TraceFrame> {
+        let mut backtrace = vec![];
+        backtrace.extend(self.trace_frame(self.instruction_start));
+        for frame in self.callstack.iter().rev() {
+            backtrace.extend(self.trace_frame(frame.ret.saturating_sub(1)));
+        }
+        backtrace
+    }
+
+    fn comparison(&mut self, symbol: &str, test: fn(Ordering) -> bool) -> Result<(), NovaError> {
+        if let Some((arg1, arg2)) = self.state.pop2() {
+            match arg2.compare(&arg1) {
+                Some(ordering) => {
+                    self.state.push_fast(VmSmall::Bool(test(ordering)));
+                    Ok(())
+                }
+                None => Err(common::error::runetime_error(format!(
+                    "Cannot compare {:?} {} {:?}",
+                    arg2, symbol, arg1
+                ))),
+            }
+        } else {
+            Err(common::error::runetime_error(format!(
+                "Not enough arguments for {}",
+                symbol
+            )))
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), NovaError> {
+        let result = self.execute();
+        self.failed = result.is_err();
+        if let Some(trace) = &mut self.trace {
+            trace.flush()?;
+        }
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => Err(error.with_backtrace(self.backtrace())),
+        }
+    }
+
+    fn execute(&mut self) -> Result<(), NovaError> {
         //let mut calls: u128 = 0;
         loop {
             // calls += 1;
             // let start = Instant::now();
+            if self.callstack.len() < self.floor {
+                break;
+            }
+            self.instruction_start = self.current_instruction;
+            self.check_limits()?;
+            let start = self.analizer.as_ref().map(|_| Instant::now());
+            if self.trace.is_some() {
+                self.trace_instruction()?;
+            }
+            if self.hook.is_some() {
+                self.run_hook()?;
+            }
 
-            //println!("current current_instruction: {}, Instruction: {} Calls: {}", self.current_instruction, self.program[self.current_instruction], calls);
-            match self.next() {
+            let opcode = match self.byte() {
+                Some(opcode) => opcode,
+                None => {
+                    return Err(common::error::runetime_error(format!(
+                        "Program ends without a return at {}",
+                        self.instruction_start
+                    )))
+                }
+            };
+            match opcode {
                 Code::RET => {
                     if let Some(ret) = self.callstack.pop() {
                         match ret.kind {
@@ -74,7 +718,7 @@ impl Vm {
                             CallType::For(reg, array, currentindex) => {
                                 if currentindex < array.len() {
                                     self.state
-                                        .store_in_register(reg, array[currentindex].clone());
+                                        .store_in_register(reg, array[currentindex].clone())?;
                                     self.callstack.push(frame::Frame {
                                         kind: frame::CallType::For(reg, array, currentindex + 1),
                                         target: ret.target,
@@ -82,6 +726,7 @@ impl Vm {
                                     });
                                     self.goto(ret.target);
                                 } else {
+                                    self.state.release(VmBig::List(array));
                                     self.goto(ret.ret);
                                 }
                             }
@@ -113,7 +758,8 @@ impl Vm {
                                 self.state.deallocate_upvalue();
                                 self.goto(ret.ret);
                             }
-                            CallType::For(_, _, _) => {
+                            CallType::For(_, array, _) => {
+                                self.state.release(VmBig::List(array));
                                 self.goto(ret.ret);
                             }
                             CallType::Loop => {
@@ -125,40 +771,27 @@ impl Vm {
                     }
                 }
                 Code::INTEGER => {
-                    let int = i64::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let int = self.int()?;
 
                     self.state.push_fast(VmSmall::Int(int));
                 }
                 Code::BYTE => {
-                    let int = self.next() as i64;
+                    let int = self.next()? as i64;
                     self.state.push_fast(VmSmall::Int(int));
                 }
                 Code::FLOAT => {
-                    let fl = f64::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let fl = self.float()?;
                     self.state.push_fast(VmSmall::Float(fl));
                 }
                 Code::ADD => {
                     if let Some(args) = self.state.pop_fast2() {
                         let result = match args {
-                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => VmSmall::Int(arg1 + arg2),
+                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => {
+                                match arg1.checked_add(arg2) {
+                                    Some(result) => VmSmall::Int(result),
+                                    None => return Err(overflow(arg2, "+", arg1)),
+                                }
+                            }
                             (VmSmall::Int(arg1), VmSmall::Float(arg2)) => {
                                 VmSmall::Float(arg1 as f64 + arg2)
                             }
@@ -185,7 +818,12 @@ impl Vm {
                 Code::SUB => {
                     if let Some(args) = self.state.pop_fast2() {
                         let result = match args {
-                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => VmSmall::Int(arg2 - arg1),
+                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => {
+                                match arg2.checked_sub(arg1) {
+                                    Some(result) => VmSmall::Int(result),
+                                    None => return Err(overflow(arg2, "-", arg1)),
+                                }
+                            }
                             (VmSmall::Int(arg1), VmSmall::Float(arg2)) => {
                                 VmSmall::Float(arg2 - arg1 as f64)
                             }
@@ -212,7 +850,12 @@ impl Vm {
                 Code::MUL => {
                     if let Some(args) = self.state.pop_fast2() {
                         let result = match args {
-                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => VmSmall::Int(arg1 * arg2),
+                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => {
+                                match arg1.checked_mul(arg2) {
+                                    Some(result) => VmSmall::Int(result),
+                                    None => return Err(overflow(arg2, "*", arg1)),
+                                }
+                            }
                             (VmSmall::Int(arg1), VmSmall::Float(arg2)) => {
                                 VmSmall::Float(arg1 as f64 * arg2)
                             }
@@ -239,8 +882,17 @@ impl Vm {
                 Code::DIV => {
                     if let Some(args) = self.state.pop_fast2() {
                         let result = match args {
+                            (VmSmall::Int(0), VmSmall::Int(arg2)) => {
+                                return Err(common::error::runetime_error(format!(
+                                    "Division by zero: {} / 0",
+                                    arg2
+                                )));
+                            }
                             (VmSmall::Int(arg1), VmSmall::Int(arg2)) => {
-                                VmSmall::Float((arg2 / arg1) as f64)
+                                match arg2.checked_div(arg1) {
+                                    Some(result) => VmSmall::Float(result as f64),
+                                    None => return Err(overflow(arg2, "/", arg1)),
+                                }
                             }
                             (VmSmall::Int(arg1), VmSmall::Float(arg2)) => {
                                 VmSmall::Float(arg2 / arg1 as f64)
@@ -266,21 +918,12 @@ impl Vm {
                     }
                 }
                 Code::STOREID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let index = self.uint()?;
                     self.state.push_fast(VmSmall::Register(index));
                 }
                 Code::ID => {
-                    let index = u16::from_ne_bytes([self.next(), self.next()]);
-                    let item = self.state.get_from_register(index as usize);
+                    let index = self.uint()?;
+                    let item = self.state.get_from_register(index)?;
                     //println!("getting {}", index);
                     self.state.push(item);
                 }
@@ -288,11 +931,11 @@ impl Vm {
                     if let (Some(arg1), Some(arg2)) = (self.state.pop(), self.state.pop_fast()) {
                         match (arg1, arg2) {
                             (data, VmSmall::Register(index)) => {
-                                self.state.store_in_register(index, data.clone());
+                                self.state.store_in_register(index, data)?;
                                 //println!("storing {:?} in reg {}", data, index);
                             }
                             (data, VmSmall::Global(index)) => {
-                                self.state.store_in_global(index, data.clone());
+                                self.state.store_in_global(index, data)?;
                                 //println!("storing {:?} in reg {}", data, index);
                             }
                             (a, b) => {
@@ -309,33 +952,20 @@ impl Vm {
                     }
                 }
                 Code::ALLOCATEREG => {
-                    let allocations = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let allocations = self.uint()?;
 
                     self.state.allocate_registers(allocations);
                 }
+                Code::GROWREG => {
+                    let allocations = self.uint()?;
+
+                    self.state.grow_registers(allocations);
+                }
                 Code::BLOCK => {
-                    self.state.push(VmBig::Block(self.current_instruction + 8));
-                    let jump = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-
-                    self.current_instruction += jump;
+                    let jump = self.uint()?;
+                    self.state.push(VmBig::Block(self.current_instruction));
+
+                    self.jump(jump)?;
                 }
                 Code::CALL => {
                     if let Some(callee) = self.state.pop() {
@@ -366,24 +996,22 @@ impl Vm {
                                 self.goto(target);
                             }
                             a => {
-                                dbg!(a);
-                                todo!()
+                                return Err(operand_error(
+                                    "Call",
+                                    "Function, Block or Closure",
+                                    format!("{:?}", a),
+                                ));
                             }
                         }
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for call".to_string(),
+                        ));
                     }
                 }
                 Code::DIRECTCALL => {
-                    let target = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-                    let target = self.state.get_from_register(target);
+                    let register = self.uint()?;
+                    let target = self.state.get_from_register(register)?;
 
                     match target {
                         VmBig::Function(target) => {
@@ -415,33 +1043,85 @@ impl Vm {
                             if let Some(index) = self.state.pop_fast() {
                                 match index {
                                     VmSmall::Int(index) => {
-                                        self.state.push(list[index as usize].clone());
+                                        if let Some(item) = usize::try_from(index)
+                                            .ok()
+                                            .and_then(|index| list.get(index))
+                                        {
+                                            self.state.push(item.clone());
+                                        } else {
+                                            return Err(common::error::runetime_error(format!(
+                                                "Index {} is out of bounds for {} of length {}",
+                                                index,
+                                                self.register_name(register),
+                                                list.len()
+                                            )));
+                                        }
                                     }
-                                    _ => {
-                                        return Err(common::error::runetime_error(
-                                            "Not enough arguments for list".to_string(),
+                                    a => {
+                                        return Err(operand_error(
+                                            "List index",
+                                            "Int",
+                                            format!("{:?}", a),
                                         ));
                                     }
                                 }
+                            } else {
+                                return Err(common::error::runetime_error(
+                                    "Not enough arguments for list".to_string(),
+                                ));
+                            }
+                        }
+                        VmBig::Map(map) => {
+                            if let Some(key) = self.state.pop() {
+                                let key = key.to_key()?;
+                                if let Some(item) = map.get(&key) {
+                                    self.state.push(item.clone());
+                                } else {
+                                    return Err(common::error::runetime_error(format!(
+                                        "Key {:?} is not in {}",
+                                        key.to_value(),
+                                        self.register_name(register)
+                                    )));
+                                }
+                            } else {
+                                return Err(common::error::runetime_error(
+                                    "Not enough arguments for map".to_string(),
+                                ));
                             }
                         }
+                        VmBig::UserData(data) => {
+                            let name = match self.state.pop() {
+                                Some(VmBig::String(name)) => name,
+                                a => {
+                                    return Err(operand_error(
+                                        &format!("Method call on {}", self.register_name(register)),
+                                        "String",
+                                        format!("{:?}", a),
+                                    ));
+                                }
+                            };
+                            let Some(method) = data.method(&name) else {
+                                return Err(common::error::runetime_error(format!(
+                                    "{} has no method {}",
+                                    data.name(),
+                                    name
+                                )));
+                            };
+                            self.state.push(VmBig::UserData(data));
+                            method(&mut self.state)?
+                        }
                         a => {
-                            dbg!(a);
-                            todo!()
+                            return Err(operand_error(
+                                &format!("Direct call of {}", self.register_name(register)),
+                                "Function, Block, Closure, List, Map or UserData",
+                                format!("{:?}", a),
+                            ));
                         }
                     }
                 }
                 Code::NEWLIST => {
-                    let size = u64::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let size = self.uint()?;
+                    self.state.allocate(size.saturating_mul(state::ITEM))?;
 
                     let mut myarray = vec![];
 
@@ -449,11 +1129,33 @@ impl Vm {
                         if let Some(value) = self.state.pop() {
                             myarray.push(value)
                         } else {
-                            todo!()
+                            return Err(common::error::runetime_error(
+                                "Not enough arguments for list".to_string(),
+                            ));
                         }
                     }
                     myarray.reverse();
-                    self.state.push(VmBig::List(myarray));
+                    self.state.push(VmBig::List(Rc::new(myarray)));
+                }
+                Code::NEWMAP => {
+                    let size = self.uint()?;
+                    self.state.allocate(size.saturating_mul(state::ENTRY))?;
+
+                    let mut pairs = vec![];
+
+                    for _ in 0..size {
+                        if let Some((value, key)) = self.state.pop2() {
+                            pairs.push((key.to_key()?, value))
+                        } else {
+                            return Err(common::error::runetime_error(
+                                "Not enough arguments for map".to_string(),
+                            ));
+                        }
+                    }
+                    // later entries win over earlier ones with the same key
+                    pairs.reverse();
+                    self.state
+                        .push(VmBig::Map(Rc::new(pairs.into_iter().collect())));
                 }
                 Code::TRUE => {
                     self.state.push_fast(VmSmall::Bool(true));
@@ -462,87 +1164,37 @@ impl Vm {
                     self.state.push_fast(VmSmall::Bool(false));
                 }
                 Code::STOREFASTID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let index = self.uint()?;
                     if let Some(item) = self.state.pop() {
-                        self.state.store_in_register(index, item.clone());
+                        self.state.store_in_register(index, item.clone())?;
                     }
                 }
                 Code::FUNCTION => {
-                    self.state
-                        .push(VmBig::Function(self.current_instruction + 8));
-
-                    let jump = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-
-                    self.current_instruction += jump;
-                }
-                Code::GTR => {
-                    if let Some(args) = self.state.pop_fast2() {
-                        let result = match args {
-                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => VmSmall::Bool(arg2 > arg1),
-                            (VmSmall::Int(arg1), VmSmall::Float(arg2)) => {
-                                VmSmall::Bool(arg2 > arg1 as f64)
-                            }
-                            (VmSmall::Float(arg1), VmSmall::Int(arg2)) => {
-                                VmSmall::Bool(arg2 as f64 > arg1)
-                            }
-                            (VmSmall::Float(arg1), VmSmall::Float(arg2)) => {
-                                VmSmall::Bool(arg2 > arg1)
-                            }
-                            _ => {
-                                todo!()
-                            }
-                        };
-                        self.state.push_fast(result);
-                    }
-                }
-                Code::LSS => {
-                    if let Some(args) = self.state.pop_fast2() {
-                        let result = match args {
-                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => VmSmall::Bool(arg2 < arg1),
-                            (VmSmall::Int(arg1), VmSmall::Float(arg2)) => {
-                                VmSmall::Bool(arg2 < arg1 as f64)
-                            }
-                            (VmSmall::Float(arg1), VmSmall::Int(arg2)) => {
-                                VmSmall::Bool((arg2 as f64) < arg1)
-                            }
-                            (VmSmall::Float(arg1), VmSmall::Float(arg2)) => {
-                                VmSmall::Bool(arg2 < arg1)
-                            }
-                            _ => {
-                                todo!()
-                            }
-                        };
-                        self.state.push_fast(result);
-                    }
+                    let jump = self.uint()?;
+                    self.state.push(VmBig::Function(self.current_instruction));
+
+                    self.jump(jump)?;
                 }
+                Code::GTR => self.comparison(">", Ordering::is_gt)?,
+                Code::LSS => self.comparison("<", Ordering::is_lt)?,
+                Code::GTE => self.comparison(">=", Ordering::is_ge)?,
+                Code::LTE => self.comparison("<=", Ordering::is_le)?,
                 Code::JUMPIFFALSE => {
-                    let jump =
-                        u32::from_ne_bytes([self.next(), self.next(), self.next(), self.next()]);
+                    let jump = self.uint()?;
 
                     if let Some(VmSmall::Bool(false)) = self.state.pop_fast() {
-                        self.current_instruction += jump as usize;
+                        self.jump(jump)?;
                     }
                 }
                 Code::REC => {
-                    let frame = self.callstack[self.callstack.len() - 1].clone();
+                    let frame = match self.callstack.last() {
+                        Some(frame) => frame.clone(),
+                        None => {
+                            return Err(common::error::runetime_error(
+                                "Rec called outside of a function or block".to_string(),
+                            ));
+                        }
+                    };
                     match frame.kind {
                         CallType::Function => {
                             self.callstack.push(frame::Frame {
@@ -560,9 +1212,27 @@ impl Vm {
                             });
                             self.goto(frame.target);
                         }
-                        CallType::Closure => todo!(),
-                        CallType::For(_, _, _) => todo!(),
-                        CallType::Loop => todo!(),
+                        CallType::Closure => {
+                            return Err(operand_error(
+                                "Rec",
+                                "a Function or Block frame",
+                                "Closure".to_string(),
+                            ));
+                        }
+                        CallType::For(_, _, _) => {
+                            return Err(operand_error(
+                                "Rec",
+                                "a Function or Block frame",
+                                "For".to_string(),
+                            ));
+                        }
+                        CallType::Loop => {
+                            return Err(operand_error(
+                                "Rec",
+                                "a Function or Block frame",
+                                "Loop".to_string(),
+                            ));
+                        }
                     };
                 }
                 Code::WHEN => {
@@ -578,10 +1248,18 @@ impl Vm {
                                     self.goto(callee);
                                 }
                             }
-                            _ => {
-                                todo!()
+                            (a, b) => {
+                                return Err(operand_error(
+                                    "When",
+                                    "(Bool, Block)",
+                                    format!("({:?}, {:?})", b, a),
+                                ));
                             }
                         }
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for when".to_string(),
+                        ));
                     }
                 }
                 Code::IF => {
@@ -604,17 +1282,25 @@ impl Vm {
                                     self.goto(elseb);
                                 }
                             }
-                            _ => {
-                                todo!()
+                            (a, b, c) => {
+                                return Err(operand_error(
+                                    "If",
+                                    "(Bool, Block, Block)",
+                                    format!("({:?}, {:?}, {:?})", c, b, a),
+                                ));
                             }
                         }
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for if".to_string(),
+                        ));
                     }
                 }
 
                 Code::EQUALS => {
                     if let Some(args) = self.state.pop2() {
                         let (one, two) = args;
-                        if one == two {
+                        if one.equals(&two) {
                             self.state.push_fast(VmSmall::Bool(true))
                         } else {
                             self.state.push_fast(VmSmall::Bool(false))
@@ -622,76 +1308,80 @@ impl Vm {
                     }
                 }
 
+                Code::NOTEQUALS => {
+                    if let Some(args) = self.state.pop2() {
+                        let (one, two) = args;
+                        self.state.push_fast(VmSmall::Bool(!one.equals(&two)))
+                    }
+                }
+
                 Code::MODULO => {
                     if let Some(args) = self.state.pop_fast2() {
                         let result = match args {
-                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => {
-                                VmSmall::Int(modulo::Mod::modulo(arg2, arg1))
+                            (VmSmall::Int(0), VmSmall::Int(arg2)) => {
+                                return Err(common::error::runetime_error(format!(
+                                    "Modulo by zero: {} % 0",
+                                    arg2
+                                )));
                             }
+                            (VmSmall::Int(arg1), VmSmall::Int(arg2)) => match modulo(arg2, arg1) {
+                                Some(result) => VmSmall::Int(result),
+                                None => return Err(overflow(arg2, "%", arg1)),
+                            },
                             (a, b) => {
-                                println!("{:?} {:?}", a, b);
-                                todo!()
+                                return Err(common::error::runetime_error(format!(
+                                    "Cannot modulo {:?} % {:?}",
+                                    b, a
+                                )));
                             }
                         };
                         self.state.push_fast(result)
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for modulo".to_string(),
+                        ));
                     }
                 }
 
                 Code::REFID => {
-                    let index = u16::from_ne_bytes([self.next(), self.next()]);
-                    self.state.push_fast(VmSmall::Register(index as usize))
+                    let index = self.uint()?;
+                    self.state.push_fast(VmSmall::Register(index))
                 }
                 Code::CLOSURE => {
+                    let jump = self.uint()?;
                     if let Some(VmBig::List(array)) = self.state.pop() {
                         self.state
-                            .push(VmBig::Closure(self.current_instruction + 8, array));
+                            .push(VmBig::Closure(self.current_instruction, array));
                     }
-                    let jump = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-                    self.current_instruction += jump;
+                    self.jump(jump)?;
                 }
                 Code::CID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-                    self.state.upvalue_to_stack(index);
+                    let index = self.uint()?;
+                    self.state.upvalue_to_stack(index)?;
                 }
 
                 Code::STRING => {
-                    let mut string = vec![];
-                    let size = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-                    for _ in 0..size {
-                        string.push(self.next());
-                    }
-                    let string = match String::from_utf8(string) {
+                    let size = self.uint()?;
+                    let bytes = match self
+                        .program
+                        .get(self.current_instruction..)
+                        .and_then(|rest| rest.get(..size))
+                    {
+                        Some(bytes) => bytes.to_vec(),
+                        None => return Err(self.malformed()),
+                    };
+                    self.current_instruction += size;
+                    let string = match String::from_utf8(bytes) {
                         Ok(ok) => ok,
-                        Err(_) => todo!(),
+                        Err(error) => {
+                            return Err(common::error::runetime_error(format!(
+                                "String constant is not valid utf-8: {}",
+                                error
+                            )));
+                        }
                     };
-                    self.state.push(VmBig::String(string));
+                    self.state.allocate(string.len())?;
+                    self.state.push(VmBig::String(Rc::new(string)));
                 }
 
                 Code::FOR => {
@@ -707,7 +1397,7 @@ impl Vm {
                                 VmSmall::Block(target),
                             ) => {
                                 if !array.is_empty() {
-                                    self.state.store_in_register(reg, array[0].clone());
+                                    self.state.store_in_register(reg, array[0].clone())?;
                                     self.callstack.push(frame::Frame {
                                         kind: frame::CallType::For(reg, array, 1),
                                         target,
@@ -718,12 +1408,17 @@ impl Vm {
                                 }
                             }
                             (a, b, c) => {
-                                dbg!(a, b, c);
-                                todo!()
+                                return Err(operand_error(
+                                    "For",
+                                    "(Register, List, Block)",
+                                    format!("({:?}, {:?}, {:?})", a, b, c),
+                                ));
                             }
                         }
                     } else {
-                        todo!()
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for for".to_string(),
+                        ));
                     }
                 }
 
@@ -731,102 +1426,132 @@ impl Vm {
                     if let Some(args) = self.state.pop_fast2() {
                         match args {
                             (VmSmall::Int(to), VmSmall::Int(from)) => {
+                                let count = (to as i128 - from as i128 + 1).max(0);
+                                self.state.allocate(
+                                    usize::try_from(count)
+                                        .unwrap_or(usize::MAX)
+                                        .saturating_mul(state::ITEM),
+                                )?;
                                 let mut array = vec![];
                                 for i in from..=to {
                                     array.push(VmBig::Int(i));
                                 }
-                                self.state.push(VmBig::List(array));
+                                self.state.push(VmBig::List(Rc::new(array)));
                             }
-                            _ => {
-                                todo!()
+                            (a, b) => {
+                                return Err(operand_error(
+                                    "Range",
+                                    "(Int, Int)",
+                                    format!("({:?}, {:?})", b, a),
+                                ));
                             }
                         }
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for range".to_string(),
+                        ));
                     }
                 }
 
+                Code::TAKEID => {
+                    self.take = Some(self.uint()?);
+                }
                 Code::NATIVE => {
-                    let index = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-
-                    match self.native_functions[index](&mut self.state) {
+                    let index = self.uint()?;
+                    let take = self.take.take();
+                    self.check_arguments(index)?;
+
+                    // the register about to be overwritten lets go of its
+                    // value, so a list passed in can be changed in place.
+                    // Natives that can run Nova code still see it
+                    let lent = match (take, self.native_functions.get(index)) {
+                        (Some(register), Some(Native::State(_) | Native::Closure(_))) => Some((
+                            register,
+                            Lent::new(self.state.take_from_register(register)?),
+                        )),
+                        _ => None,
+                    };
+                    let result = match self.native_functions.get(index) {
+                        Some(Native::State(function)) => function(&mut self.state),
+                        Some(Native::Vm(function)) => {
+                            let function = *function;
+                            function(self)
+                        }
+                        // a closure native cannot reach the VM, so it is never
+                        // borrowed twice
+                        Some(Native::Closure(function)) => (function.borrow_mut())(&mut self.state),
+                        None => {
+                            return Err(common::error::runetime_error(format!(
+                                "Native function {} is not registered",
+                                index
+                            )));
+                        }
+                    };
+                    match result {
                         Ok(_) => {}
-                        Err(error) => return Err(error),
+                        Err(error) => {
+                            if let Some((register, lent)) = lent {
+                                if let Some(value) = lent.back() {
+                                    self.state.store_in_register(register, value)?;
+                                }
+                            }
+                            return Err(error);
+                        }
                     }
                 }
                 Code::ALLOCATEGLOBAL => {
-                    let size = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let size = self.uint()?;
 
                     self.state.allocate_globals(size);
                 }
 
                 Code::GLOBALID => {
-                    let index = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let index = self.uint()?;
 
-                    self.state.global_to_stack(index);
+                    self.state.global_to_stack(index)?;
                 }
 
                 Code::STOREGLOBAL => {
-                    let index = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
+                    let index = self.uint()?;
 
                     self.state.push_fast(VmSmall::Global(index))
                 }
 
                 Code::CHAR => {
-                    let c = self.next();
+                    let c = self.char()?;
 
-                    self.state.push_fast(VmSmall::Char(c as char))
+                    self.state.push_fast(VmSmall::Char(c))
                 }
 
                 Code::POP => {
-                    self.state.pop();
+                    self.state.discard();
                 }
                 Code::NEG => {
                     if let Some(item) = self.state.pop_fast() {
                         let result = match item {
-                            VmSmall::Int(int) => VmSmall::Int(-int),
+                            VmSmall::Int(int) => match int.checked_neg() {
+                                Some(result) => VmSmall::Int(result),
+                                None => {
+                                    return Err(common::error::runetime_error(format!(
+                                        "Integer overflow: -({})",
+                                        int
+                                    )))
+                                }
+                            },
                             VmSmall::Float(float) => VmSmall::Float(-float),
-                            _ => {
-                                todo!()
+                            a => {
+                                return Err(operand_error(
+                                    "Neg",
+                                    "Int or Float",
+                                    format!("{:?}", a),
+                                ));
                             }
                         };
                         self.state.push_fast(result)
                     } else {
-                        todo!()
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for negation".to_string(),
+                        ));
                     }
                 }
                 Code::NEWBINDING => {
@@ -836,24 +1561,17 @@ impl Vm {
                     self.state.pop_bindings();
                 }
                 Code::GETBIND => {
-                    let index = usize::from_ne_bytes([
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                        self.next(),
-                    ]);
-
-                    self.state.binding_to_stack(index);
+                    let index = self.uint()?;
+
+                    self.state.binding_to_stack(index)?;
                 }
                 Code::STOREBIND => {
                     if let Some(item) = self.state.pop() {
-                        self.state.push_binding(item);
+                        self.state.push_binding(item)?;
                     } else {
-                        todo!()
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for binding".to_string(),
+                        ));
                     }
                 }
 
@@ -868,24 +1586,109 @@ impl Vm {
                                 });
                                 self.goto(target)
                             }
-                            _ => {
-                                todo!()
+                            a => {
+                                return Err(operand_error("Loop", "Block", format!("{:?}", a)));
                             }
                         }
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for loop".to_string(),
+                        ));
                     }
                 }
-                _ => {}
-            }
+                Code::AND => {
+                    if let Some(args) = self.state.pop_fast2() {
+                        let result = match args {
+                            (VmSmall::Bool(arg1), VmSmall::Bool(arg2)) => {
+                                VmSmall::Bool(arg2 && arg1)
+                            }
+                            (a, b) => {
+                                return Err(common::error::runetime_error(format!(
+                                    "Cannot and {:?} && {:?}",
+                                    b, a
+                                )));
+                            }
+                        };
+                        self.state.push_fast(result)
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for and".to_string(),
+                        ));
+                    }
+                }
+                Code::OR => {
+                    if let Some(args) = self.state.pop_fast2() {
+                        let result = match args {
+                            (VmSmall::Bool(arg1), VmSmall::Bool(arg2)) => {
+                                VmSmall::Bool(arg2 || arg1)
+                            }
+                            (a, b) => {
+                                return Err(common::error::runetime_error(format!(
+                                    "Cannot or {:?} || {:?}",
+                                    b, a
+                                )));
+                            }
+                        };
+                        self.state.push_fast(result)
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for or".to_string(),
+                        ));
+                    }
+                }
+                Code::NOT => {
+                    if let Some(item) = self.state.pop_fast() {
+                        let result = match item {
+                            VmSmall::Bool(bool) => VmSmall::Bool(!bool),
+                            a => {
+                                return Err(operand_error("Not", "Bool", format!("{:?}", a)));
+                            }
+                        };
+                        self.state.push_fast(result)
+                    } else {
+                        return Err(common::error::runetime_error(
+                            "Not enough arguments for not".to_string(),
+                        ));
+                    }
+                }
+                // the left operand stays on the stack, either as the result or
+                // to be combined with the right operand
+                Code::SHORTAND | Code::SHORTOR => {
+                    let skip_on = self.program[self.instruction_start] == Code::SHORTOR;
+                    let jump = self.uint()?;
 
-            // println!("current offset {}",self.offset);
+                    match self.state.pop_fast() {
+                        Some(VmSmall::Bool(test)) => {
+                            self.state.push_fast(VmSmall::Bool(test));
+                            if test == skip_on {
+                                self.jump(jump)?;
+                            }
+                        }
+                        Some(a) => {
+                            let opcode = if skip_on { "Or" } else { "And" };
+                            return Err(operand_error(opcode, "Bool", format!("{:?}", a)));
+                        }
+                        None => {
+                            return Err(common::error::runetime_error(
+                                "Not enough arguments for short circuit".to_string(),
+                            ));
+                        }
+                    }
+                }
+                Code::BOUNCE => {}
+                unknown => {
+                    return Err(common::error::runetime_error(format!(
+                        "Unknown opcode {} at {}",
+                        unknown,
+                        self.current_instruction - 1
+                    )));
+                }
+            }
 
-            // let duration = start.elapsed();
-            // if let Some(data) = self.analizer.get_mut(&self.program[self.current_instruction]) {
-            //     *data += duration;
-            // } else {
-            //     let new = std::time::Duration::new(0, 0);
-            //     self.analizer.insert(self.program[self.current_instruction], new);
-            // }
+            if let (Some(profile), Some(start)) = (&mut self.analizer, start) {
+                profile.instruction(self.program[self.instruction_start], start.elapsed());
+                profile.sync(&self.callstack);
+            }
         }
 
         // if let Some(scope) = self.variable_map.last() {
@@ -899,9 +1702,6 @@ impl Vm {
         //println!("STACK: {:?}", self.stack)
         // println!("Dispatch: {}", self.dispatch);
         // println!("Total Calls: {}", calls);
-        // for data in self.analizer.iter() {
-        //     println!("Instruction: {} Time elapsed: {:?}", data.0, data.1)
-        // }
         Ok(())
     }
 }
@@ -0,0 +1,213 @@
+use crate::frame::Frame;
+use common::debug::DebugInfo;
+use fxhash::FxHashMap;
+use std::time::Duration;
+
+pub fn new() -> Profile {
+    Profile {
+        opcodes: FxHashMap::default(),
+        nodes: vec![Node {
+            parent: 0,
+            target: None,
+            calls: 1,
+            time: Duration::ZERO,
+        }],
+        children: FxHashMap::default(),
+        path: vec![0],
+    }
+}
+
+/// Counts and times every instruction the VM runs. Time is charged to the
+/// opcode and to the call path the instruction ran in, kept as a tree of
+/// call targets rooted at the main program.
+pub struct Profile {
+    opcodes: FxHashMap<u8, (u64, Duration)>,
+    nodes: Vec<Node>,
+    children: FxHashMap<(usize, usize), usize>,
+    path: Vec<usize>,
+}
+
+struct Node {
+    parent: usize,
+    target: Option<usize>,
+    calls: u64,
+    time: Duration,
+}
+
+#[derive(Default)]
+struct Function {
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration,
+}
+
+fn name(target: Option<usize>, debug: &DebugInfo) -> String {
+    let target = match target {
+        Some(target) => target,
+        None => return "<main>".to_string(),
+    };
+    match debug.chunks.iter().find(|chunk| chunk.start == target) {
+        Some(chunk) => debug.label(chunk),
+        None => format!("<block>@{}", target),
+    }
+}
+
+fn location(target: Option<usize>, debug: &DebugInfo) -> String {
+    let offset = match target {
+        Some(target) => target,
+        None => debug
+            .lines
+            .first()
+            .map(|entry| entry.offset)
+            .unwrap_or_default(),
+    };
+    match debug.line_at(offset) {
+        Some((line, file)) => format!("{}:{}", file, line),
+        None => "-".to_string(),
+    }
+}
+
+impl Profile {
+    #[inline(always)]
+    pub(crate) fn instruction(&mut self, opcode: u8, time: Duration) {
+        let entry = self.opcodes.entry(opcode).or_default();
+        entry.0 += 1;
+        entry.1 += time;
+        if let Some(&node) = self.path.last() {
+            self.nodes[node].time += time
+        }
+    }
+
+    /// Follows the call stack after an instruction, counting a call for
+    /// every frame that was pushed. Loops and for bodies reuse their frame,
+    /// so each counts once however many times it repeats.
+    #[inline(always)]
+    pub(crate) fn sync(&mut self, callstack: &[Frame]) {
+        self.path.truncate(callstack.len() + 1);
+        while self.path.len() > 1 {
+            let node = self.path[self.path.len() - 1];
+            if self.nodes[node].target == Some(callstack[self.path.len() - 2].target) {
+                break;
+            }
+            self.path.pop();
+        }
+        while self.path.len() <= callstack.len() {
+            let parent = self.path[self.path.len() - 1];
+            let target = callstack[self.path.len() - 1].target;
+            let node = match self.children.get(&(parent, target)) {
+                Some(&node) => node,
+                None => {
+                    self.nodes.push(Node {
+                        parent,
+                        target: Some(target),
+                        calls: 0,
+                        time: Duration::ZERO,
+                    });
+                    self.children.insert((parent, target), self.nodes.len() - 1);
+                    self.nodes.len() - 1
+                }
+            };
+            self.nodes[node].calls += 1;
+            self.path.push(node)
+        }
+    }
+
+    // time of every node including the nodes called from it
+    fn totals(&self) -> Vec<Duration> {
+        let mut totals: Vec<Duration> = self.nodes.iter().map(|node| node.time).collect();
+        for index in (1..self.nodes.len()).rev() {
+            let total = totals[index];
+            totals[self.nodes[index].parent] += total;
+        }
+        totals
+    }
+
+    fn functions(&self) -> Vec<(Option<usize>, Function)> {
+        let totals = self.totals();
+        let mut functions: FxHashMap<Option<usize>, Function> = FxHashMap::default();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let function = functions.entry(node.target).or_default();
+            function.calls += node.calls;
+            function.exclusive += node.time;
+
+            // recursive calls are already counted by the outermost one
+            let mut ancestor = node.parent;
+            let mut recursive = false;
+            while index != 0 && !recursive {
+                recursive = self.nodes[ancestor].target == node.target;
+                if ancestor == 0 {
+                    break;
+                }
+                ancestor = self.nodes[ancestor].parent;
+            }
+            if !recursive {
+                function.inclusive += totals[index];
+            }
+        }
+        let mut functions: Vec<_> = functions.into_iter().collect();
+        functions.sort_by_key(|function| std::cmp::Reverse(function.1.inclusive));
+        functions
+    }
+
+    /// The profile as two tables, opcodes and then functions, each sorted by
+    /// the time spent in them.
+    pub fn table(&self, debug: &DebugInfo) -> String {
+        let mut output = String::new();
+        let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+        opcodes.sort_by_key(|opcode| std::cmp::Reverse(opcode.1 .1));
+        output.push_str(&format!(
+            "{:<16} {:>12} {:>12} {:>10}\n",
+            "Opcode", "Count", "Time", "Average"
+        ));
+        for (opcode, (count, time)) in opcodes {
+            output.push_str(&format!(
+                "{:<16} {:>12} {:>12} {:>10}\n",
+                common::code::name(*opcode).unwrap_or("UNKNOWN"),
+                count,
+                format!("{:.2?}", time),
+                format!(
+                    "{:.0?}",
+                    Duration::from_nanos((time.as_nanos() / (*count).max(1) as u128) as u64)
+                )
+            ))
+        }
+
+        output.push('\n');
+        output.push_str(&format!(
+            "{:<24} {:<24} {:>10} {:>12} {:>12}\n",
+            "Function", "Location", "Calls", "Inclusive", "Exclusive"
+        ));
+        for (target, function) in self.functions() {
+            output.push_str(&format!(
+                "{:<24} {:<24} {:>10} {:>12} {:>12}\n",
+                name(target, debug),
+                location(target, debug),
+                function.calls,
+                format!("{:.2?}", function.inclusive),
+                format!("{:.2?}", function.exclusive)
+            ))
+        }
+        output
+    }
+
+    /// The call tree in the collapsed stack format flamegraph tools read,
+    /// one `main;caller;callee nanoseconds` line per call path.
+    pub fn collapsed(&self, debug: &DebugInfo) -> String {
+        let mut paths: Vec<String> = vec![];
+        let mut lines = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let path = match index {
+                0 => name(None, debug),
+                _ => format!("{};{}", paths[node.parent], name(node.target, debug)),
+            };
+            if !node.time.is_zero() {
+                lines.push(format!("{} {}", path, node.time.as_nanos()))
+            }
+            paths.push(path)
+        }
+        lines.sort();
+        let mut output = lines.join("\n");
+        output.push('\n');
+        output
+    }
+}
@@ -1,5 +1,15 @@
+use common::error::NovaError;
+use fxhash::FxHashSet;
+use std::{any::Any, cmp::Ordering, collections::BTreeMap, rc::Rc};
+
 pub type List = Vec<VmSmall>;
 pub type LargeList = Vec<VmBig>;
+pub type Map = BTreeMap<MapKey, VmBig>;
+
+/// Bytes a list item takes, as charged by `State::allocate`.
+pub const ITEM: usize = std::mem::size_of::<VmBig>();
+/// Bytes a map entry takes, as charged by `State::allocate`.
+pub const ENTRY: usize = std::mem::size_of::<(MapKey, VmBig)>();
 
 pub fn new() -> State {
     State {
@@ -11,10 +21,76 @@ pub fn new() -> State {
         upvalues: vec![],
         globals: vec![],
         bindings: vec![],
+        heap_limit: None,
+        allocated: 0,
+        unmeasured: 0,
+        context: None,
     }
 }
 
-#[derive(Debug)]
+// an operand naming a slot that does not exist, only hand written bytecode
+// gets here
+fn out_of_range(kind: &str, index: usize) -> NovaError {
+    common::error::runetime_error(format!("{} {} is out of range", kind, index))
+}
+
+// bytes owned by a value, values shared through an Rc are counted once
+fn measure(value: &VmBig, seen: &mut FxHashSet<usize>) -> usize {
+    match value {
+        VmBig::String(string) if seen.insert(Rc::as_ptr(string) as usize) => string.len(),
+        VmBig::List(list) | VmBig::Closure(_, list) => measure_list(list, seen),
+        VmBig::Map(map) if seen.insert(Rc::as_ptr(map) as usize) => map
+            .iter()
+            .map(|(key, value)| {
+                let key = match key {
+                    MapKey::String(string) => string.len(),
+                    _ => 0,
+                };
+                ENTRY + key + measure(value, seen)
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+fn measure_list(list: &Rc<LargeList>, seen: &mut FxHashSet<usize>) -> usize {
+    if !seen.insert(Rc::as_ptr(list) as usize) {
+        return 0;
+    }
+    list.len() * ITEM + list.iter().map(|item| measure(item, seen)).sum::<usize>()
+}
+
+// drops a value, returning the bytes that went with it. Parts still shared
+// elsewhere are kept and not counted
+fn free(value: VmBig) -> usize {
+    match value {
+        VmBig::String(string) => Rc::try_unwrap(string).map_or(0, |string| string.len()),
+        VmBig::List(list) | VmBig::Closure(_, list) => match Rc::try_unwrap(list) {
+            Ok(list) => list.len() * ITEM + list.into_iter().map(free).sum::<usize>(),
+            Err(_) => 0,
+        },
+        VmBig::Map(map) => match Rc::try_unwrap(map) {
+            Ok(map) => map
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        MapKey::String(string) => string.len(),
+                        _ => 0,
+                    };
+                    ENTRY + key + free(value)
+                })
+                .sum(),
+            Err(_) => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Bytes a value owns, as `State::allocate` counts them.
+pub fn size(value: &VmBig) -> usize {
+    measure(value, &mut FxHashSet::default())
+}
+
 pub struct State {
     stack: List,
     heap: LargeList,
@@ -22,19 +98,57 @@ pub struct State {
     bindings: Vec<LargeList>,
     window: Vec<usize>,
     offset: usize,
-    upvalues: Vec<LargeList>,
+    upvalues: Vec<Rc<LargeList>>,
     globals: LargeList,
+    heap_limit: Option<usize>,
+    allocated: usize,
+    unmeasured: usize,
+    context: Option<Box<dyn Any>>,
+}
+
+// the context is left out, it is only known to be `Any`
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("stack", &self.stack)
+            .field("heap", &self.heap)
+            .field("registers", &self.registers)
+            .field("bindings", &self.bindings)
+            .field("window", &self.window)
+            .field("offset", &self.offset)
+            .field("upvalues", &self.upvalues)
+            .field("globals", &self.globals)
+            .finish_non_exhaustive()
+    }
 }
 
 impl State {
     #[inline(always)]
-    pub fn push_binding(&mut self, item: VmBig) {
-        self.bindings.last_mut().unwrap().push(item)
+    pub fn push_binding(&mut self, item: VmBig) -> Result<(), NovaError> {
+        match self.bindings.last_mut() {
+            Some(bindings) => {
+                bindings.push(item);
+                Ok(())
+            }
+            None => Err(common::error::runetime_error(
+                "Binding outside of a let".to_string(),
+            )),
+        }
     }
 
     #[inline(always)]
-    pub fn binding_to_stack(&mut self, index: usize) {
-        self.push(self.bindings.last().unwrap()[index].clone())
+    pub fn binding_to_stack(&mut self, index: usize) -> Result<(), NovaError> {
+        match self
+            .bindings
+            .last()
+            .and_then(|bindings| bindings.get(index))
+        {
+            Some(item) => {
+                self.push(item.clone());
+                Ok(())
+            }
+            None => Err(out_of_range("Binding", index)),
+        }
     }
 
     #[inline(always)]
@@ -44,17 +158,30 @@ impl State {
 
     #[inline(always)]
     pub fn pop_bindings(&mut self) {
-        self.bindings.pop();
+        for value in self.bindings.pop().unwrap_or_default() {
+            self.release(value)
+        }
     }
 
     #[inline(always)]
-    pub fn store_in_global(&mut self, index: usize, item: VmBig) {
-        self.globals[index] = item
+    pub fn store_in_global(&mut self, index: usize, item: VmBig) -> Result<(), NovaError> {
+        let old = match self.globals.get_mut(index) {
+            Some(global) => std::mem::replace(global, item),
+            None => return Err(out_of_range("Global", index)),
+        };
+        self.release(old);
+        Ok(())
     }
 
     #[inline(always)]
-    pub fn global_to_stack(&mut self, index: usize) {
-        self.push(self.globals[index].clone())
+    pub fn global_to_stack(&mut self, index: usize) -> Result<(), NovaError> {
+        match self.globals.get(index) {
+            Some(item) => {
+                self.push(item.clone());
+                Ok(())
+            }
+            None => Err(out_of_range("Global", index)),
+        }
     }
 
     #[inline(always)]
@@ -65,7 +192,7 @@ impl State {
     }
 
     #[inline(always)]
-    pub fn allocate_upvalue(&mut self, values: LargeList) {
+    pub fn allocate_upvalue(&mut self, values: Rc<LargeList>) {
         self.upvalues.push(values)
     }
 
@@ -75,13 +202,25 @@ impl State {
     }
 
     #[inline(always)]
-    pub fn upvalue_to_stack(&mut self, index: usize) {
-        self.push(self.upvalues.last().unwrap()[index].clone())
+    pub fn upvalue_to_stack(&mut self, index: usize) -> Result<(), NovaError> {
+        match self
+            .upvalues
+            .last()
+            .and_then(|upvalues| upvalues.get(index))
+        {
+            Some(item) => {
+                self.push(item.clone());
+                Ok(())
+            }
+            None => Err(out_of_range("Upvalue", index)),
+        }
     }
 
     #[inline(always)]
     pub fn deallocate_upvalue(&mut self) {
-        self.upvalues.pop();
+        if let Some(upvalues) = self.upvalues.pop() {
+            self.release(VmBig::List(upvalues))
+        }
     }
 
     #[inline(always)]
@@ -111,6 +250,14 @@ impl State {
                 self.heap.push(data);
                 self.stack.push(VmSmall::String)
             }
+            VmBig::Map(_) => {
+                self.heap.push(data);
+                self.stack.push(VmSmall::Map)
+            }
+            VmBig::UserData(_) => {
+                self.heap.push(data);
+                self.stack.push(VmSmall::UserData)
+            }
             VmBig::Global(index) => self.stack.push(VmSmall::Global(*index)),
             VmBig::Char(c) => self.stack.push(VmSmall::Char(*c)),
         }
@@ -134,24 +281,26 @@ impl State {
     pub fn pop(&mut self) -> Option<VmBig> {
         if let Some(data) = self.stack.pop() {
             match &data {
-                VmSmall::Int(_) => Some(data.to_heap()),
-                VmSmall::Float(_) => Some(data.to_heap()),
-                VmSmall::Register(_) => Some(data.to_heap()),
-                VmSmall::Block(_) => Some(data.to_heap()),
-                VmSmall::Bool(_) => Some(data.to_heap()),
-                VmSmall::List => self.heap.pop(),
-                VmSmall::None => Some(data.to_heap()),
-                VmSmall::Function(_) => Some(data.to_heap()),
-                VmSmall::Closure => self.heap.pop(),
-                VmSmall::String => self.heap.pop(),
-                VmSmall::Global(_) => Some(data.to_heap()),
-                VmSmall::Char(_) => Some(data.to_heap()),
+                VmSmall::List
+                | VmSmall::Closure
+                | VmSmall::String
+                | VmSmall::Map
+                | VmSmall::UserData => self.heap.pop(),
+                _ => data.to_heap().ok(),
             }
         } else {
             Some(VmBig::None)
         }
     }
 
+    /// Pops the top value and drops it.
+    #[inline(always)]
+    pub fn discard(&mut self) {
+        if let Some(value) = self.pop() {
+            self.release(value)
+        }
+    }
+
     #[inline(always)]
     pub fn pop_fast3(&mut self) -> Option<(VmSmall, VmSmall, VmSmall)> {
         if let (Some(vm1), Some(vm2), Some(vm3)) =
@@ -177,6 +326,13 @@ impl State {
         self.stack.pop()
     }
 
+    /// The tags of the top `count` values, bottom first, None when the stack
+    /// holds fewer.
+    #[inline(always)]
+    pub fn peek_fast(&self, count: usize) -> Option<&[VmSmall]> {
+        self.stack.get(self.stack.len().checked_sub(count)?..)
+    }
+
     #[inline(always)]
     pub fn allocate_registers(&mut self, size: usize) {
         self.offset = self.reg_count();
@@ -186,25 +342,253 @@ impl State {
         }
     }
 
+    /// Adds registers to the current window, used when more code is compiled
+    /// into a running program.
+    #[inline(always)]
+    pub fn grow_registers(&mut self, size: usize) {
+        for _ in 0..size {
+            self.registers.push(VmBig::None)
+        }
+    }
+
+    /// Registers of the innermost call, indexed as the code indexes them.
+    pub fn registers(&self) -> &[VmBig] {
+        self.registers.get(self.offset..).unwrap_or_default()
+    }
+
+    /// Captured values of the innermost closure call.
+    pub fn upvalues(&self) -> &[VmBig] {
+        match self.upvalues.last() {
+            Some(upvalues) => upvalues,
+            None => &[],
+        }
+    }
+
+    /// Values bound by the innermost binding scope.
+    pub fn bindings(&self) -> &[VmBig] {
+        match self.bindings.last() {
+            Some(bindings) => bindings,
+            None => &[],
+        }
+    }
+
+    pub fn globals(&self) -> &[VmBig] {
+        &self.globals
+    }
+
+    /// The top `count` values of the stack, bottom first, along with how many
+    /// values are below them. Heap values are matched up with their tags.
+    pub fn top(&self, count: usize) -> (usize, LargeList) {
+        let start = self.stack.len().saturating_sub(count);
+        let tags = self.stack[start..]
+            .iter()
+            .filter(|data| {
+                matches!(
+                    data,
+                    VmSmall::List
+                        | VmSmall::Closure
+                        | VmSmall::String
+                        | VmSmall::Map
+                        | VmSmall::UserData
+                )
+            })
+            .count();
+        let mut heap = self.heap.iter().skip(self.heap.len().saturating_sub(tags));
+        let values = self.stack[start..]
+            .iter()
+            .map(|data| match data {
+                VmSmall::List
+                | VmSmall::Closure
+                | VmSmall::String
+                | VmSmall::Map
+                | VmSmall::UserData => heap.next().cloned().unwrap_or(VmBig::None),
+                data => data.to_heap().unwrap_or(VmBig::None),
+            })
+            .collect();
+        (start, values)
+    }
+
+    pub fn stack_len(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Bytes held by the lists, maps, strings and closures reachable from
+    /// the registers, globals, stack, upvalues and bindings.
+    pub fn heap_bytes(&self) -> usize {
+        let mut seen = FxHashSet::default();
+        let values = self
+            .registers
+            .iter()
+            .chain(self.globals.iter())
+            .chain(self.heap.iter())
+            .chain(self.bindings.iter().flatten());
+        let mut bytes = 0;
+        for value in values {
+            bytes += measure(value, &mut seen)
+        }
+        for upvalues in self.upvalues.iter() {
+            bytes += measure_list(upvalues, &mut seen)
+        }
+        bytes
+    }
+
+    /// Limits the bytes `heap_bytes` may reach, None removes the limit.
+    pub fn limit_heap(&mut self, bytes: Option<usize>) {
+        self.heap_limit = bytes;
+        self.allocated = self.heap_bytes();
+        self.unmeasured = 0;
+    }
+
+    pub fn heap_limit(&self) -> Option<usize> {
+        self.heap_limit
+    }
+
+    /// Accounts for `bytes` being allocated, failing with an out of memory
+    /// error when they take the heap over its limit. Bytes are added up as
+    /// they are allocated and taken off as the state drops the values owning
+    /// them. Values dropped elsewhere, by natives or instructions done with
+    /// their operands, only stop counting once the total passes the limit
+    /// and the live heap is measured again. That is skipped until a 32nd of
+    /// the limit was allocated since the last measure, so a program sitting
+    /// at its limit runs out of memory rather than measuring the heap on
+    /// every allocation. Only reachable values are measured, so grow a value
+    /// after pushing it and account for a new one before building it.
+    #[inline(always)]
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), NovaError> {
+        let limit = match self.heap_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        self.allocated = self.allocated.saturating_add(bytes);
+        self.unmeasured = self.unmeasured.saturating_add(bytes);
+        if self.allocated > limit && self.unmeasured > limit / 32 {
+            self.allocated = self.heap_bytes().saturating_add(bytes);
+            self.unmeasured = 0;
+        }
+        if self.allocated > limit {
+            return Err(common::error::runetime_error(format!(
+                "Out of memory: {} bytes needed, the heap is limited to {}",
+                self.allocated, limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Bytes counted against the heap limit, 0 when there is no limit.
+    pub fn allocated(&self) -> usize {
+        self.allocated
+    }
+
+    /// Drops a value taken off the state, what it owned stops counting
+    /// against the heap limit. Parts still shared elsewhere keep counting.
+    #[inline(always)]
+    pub fn release(&mut self, value: VmBig) {
+        if self.heap_limit.is_some() {
+            self.allocated = self.allocated.saturating_sub(free(value))
+        }
+    }
+
+    /// Stores a value the host wants its natives to reach, replacing the one
+    /// stored before.
+    pub fn set_context<T: Any>(&mut self, context: T) {
+        self.context = Some(Box::new(context))
+    }
+
+    /// The stored context, None when there is none or it is not a `T`.
+    pub fn context<T: Any>(&self) -> Option<&T> {
+        self.context.as_ref()?.downcast_ref()
+    }
+
+    pub fn context_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.context.as_mut()?.downcast_mut()
+    }
+
+    pub fn take_context(&mut self) -> Option<Box<dyn Any>> {
+        self.context.take()
+    }
+
+    /// Empties the state as `new` would, keeping the heap limit and context.
+    pub fn reset(&mut self) {
+        let context = self.context.take();
+        *self = State {
+            heap_limit: self.heap_limit,
+            context,
+            ..new()
+        }
+    }
+
+    /// Drops the stack above `height` and the registers, upvalues and
+    /// bindings of every call in progress, leaving only the outermost
+    /// register window. Heap values whose stack entry was taken by a failed
+    /// instruction are released as well.
+    pub fn unwind(&mut self, height: usize) {
+        self.stack.truncate(height);
+        let live = self
+            .stack
+            .iter()
+            .filter(|data| {
+                matches!(
+                    data,
+                    VmSmall::List
+                        | VmSmall::Closure
+                        | VmSmall::String
+                        | VmSmall::Map
+                        | VmSmall::UserData
+                )
+            })
+            .count();
+        self.heap.truncate(live);
+        if let Some(&inner) = self.window.get(1) {
+            self.registers.truncate(inner);
+            self.window.truncate(1);
+        }
+        self.offset = self.window.first().copied().unwrap_or_default();
+        self.upvalues.clear();
+        self.bindings.clear();
+        if self.heap_limit.is_some() {
+            self.allocated = self.heap_bytes();
+            self.unmeasured = 0;
+        }
+    }
+
     #[inline(always)]
     pub fn deallocate_registers(&mut self) {
         if let Some(window) = self.window.pop() {
-            let remove = self.reg_count() - window;
+            let remove = self.reg_count().saturating_sub(window);
             for _ in 0..remove {
-                self.registers.pop();
+                if let Some(value) = self.registers.pop() {
+                    self.release(value)
+                }
             }
         }
-        self.offset = *self.window.last().unwrap();
+        self.offset = self.window.last().copied().unwrap_or_default();
+    }
+
+    #[inline(always)]
+    pub fn get_from_register(&mut self, index: usize) -> Result<VmBig, NovaError> {
+        match self.registers.get(self.offset + index) {
+            Some(item) => Ok(item.clone()),
+            None => Err(out_of_range("Register", index)),
+        }
     }
 
+    /// Moves the value out of a register, leaving None in its place.
     #[inline(always)]
-    pub fn get_from_register(&mut self, index: usize) -> VmBig {
-        self.registers[self.offset + index].clone()
+    pub fn take_from_register(&mut self, index: usize) -> Result<VmBig, NovaError> {
+        match self.registers.get_mut(self.offset + index) {
+            Some(register) => Ok(std::mem::replace(register, VmBig::None)),
+            None => Err(out_of_range("Register", index)),
+        }
     }
 
     #[inline(always)]
-    pub fn store_in_register(&mut self, index: usize, item: VmBig) {
-        self.registers[self.offset + index] = item
+    pub fn store_in_register(&mut self, index: usize, item: VmBig) -> Result<(), NovaError> {
+        let old = match self.registers.get_mut(self.offset + index) {
+            Some(register) => std::mem::replace(register, item),
+            None => return Err(out_of_range("Register", index)),
+        };
+        self.release(old);
+        Ok(())
     }
 }
 
@@ -221,25 +605,53 @@ pub enum VmSmall {
     String,
     Closure,
     List,
+    Map,
+    UserData,
     None,
 }
 
 impl VmSmall {
+    /// Name of the value's type as argument errors show it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            VmSmall::Char(_) => "Char",
+            VmSmall::Int(_) => "Int",
+            VmSmall::Float(_) => "Float",
+            VmSmall::Register(_) | VmSmall::Global(_) => "Variable",
+            VmSmall::Block(_) => "Block",
+            VmSmall::Function(_) => "Function",
+            VmSmall::Bool(_) => "Bool",
+            VmSmall::String => "String",
+            VmSmall::Closure => "Closure",
+            VmSmall::List => "List",
+            VmSmall::Map => "Map",
+            VmSmall::UserData => "UserData",
+            VmSmall::None => "None",
+        }
+    }
+
+    /// Converts an inline value to its heap form. Lists, closures, strings and
+    /// maps live on the heap already and cannot be rebuilt from their stack tag.
     #[inline(always)]
-    pub fn to_heap(self) -> VmBig {
+    pub fn to_heap(self) -> Result<VmBig, NovaError> {
         match self {
-            VmSmall::Int(int) => VmBig::Int(int),
-            VmSmall::Float(fl) => VmBig::Float(fl),
-            VmSmall::Register(index) => VmBig::Register(index),
-            VmSmall::Block(index) => VmBig::Block(index),
-            VmSmall::Bool(bool) => VmBig::Bool(bool),
-            VmSmall::Function(index) => VmBig::Function(index),
-            VmSmall::None => VmBig::None,
-            VmSmall::List => todo!(),
-            VmSmall::Closure => todo!(),
-            VmSmall::String => todo!(),
-            VmSmall::Global(index) => VmBig::Global(index),
-            VmSmall::Char(c) => VmBig::Char(c),
+            VmSmall::Int(int) => Ok(VmBig::Int(int)),
+            VmSmall::Float(fl) => Ok(VmBig::Float(fl)),
+            VmSmall::Register(index) => Ok(VmBig::Register(index)),
+            VmSmall::Block(index) => Ok(VmBig::Block(index)),
+            VmSmall::Bool(bool) => Ok(VmBig::Bool(bool)),
+            VmSmall::Function(index) => Ok(VmBig::Function(index)),
+            VmSmall::None => Ok(VmBig::None),
+            VmSmall::List
+            | VmSmall::Closure
+            | VmSmall::String
+            | VmSmall::Map
+            | VmSmall::UserData => Err(common::error::runetime_error(format!(
+                "Cannot move {:?} to the heap, it is already a heap value",
+                self
+            ))),
+            VmSmall::Global(index) => Ok(VmBig::Global(index)),
+            VmSmall::Char(c) => Ok(VmBig::Char(c)),
         }
     }
 }
@@ -249,61 +661,241 @@ pub enum VmBig {
     Char(char),
     Global(usize),
     Function(usize),
-    Closure(usize, LargeList),
+    Closure(usize, Rc<LargeList>),
     Int(i64),
     Float(f64),
     Register(usize),
     Block(usize),
     Bool(bool),
-    List(LargeList),
-    String(String),
+    List(Rc<LargeList>),
+    String(Rc<String>),
+    Map(Rc<Map>),
+    UserData(UserData),
     None,
 }
 
-impl VmBig {
+/// Methods of a kind of userdata, called with the userdata on top of the
+/// arguments when Nova code calls it with the method's name.
+pub type Methods = BTreeMap<String, crate::CallBack>;
+
+/// A Rust object handed to Nova code. Scripts can only pass it around,
+/// compare it by identity and call its methods, natives downcast it to get
+/// at the object.
+#[derive(Clone)]
+pub struct UserData {
+    name: &'static str,
+    value: Rc<dyn Any>,
+    methods: Option<Rc<Methods>>,
+}
+
+impl UserData {
+    /// Wraps `value`, `name` is the type Nova errors and `println` show.
+    pub fn new<T: Any>(name: &'static str, value: T) -> UserData {
+        UserData {
+            name,
+            value: Rc::new(value),
+            methods: None,
+        }
+    }
+
+    /// Gives the userdata a methods table, usually one shared by every
+    /// object of its kind.
+    pub fn with_methods(mut self, methods: Rc<Methods>) -> UserData {
+        self.methods = Some(methods);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// The object as a shared pointer, None when it is not a `T`.
+    pub fn downcast<T: Any>(&self) -> Option<Rc<T>> {
+        self.value.clone().downcast().ok()
+    }
+
+    pub fn method(&self, name: &str) -> Option<crate::CallBack> {
+        self.methods.as_ref()?.get(name).copied()
+    }
+}
+
+/// Userdata is equal only to itself, whatever the object inside.
+impl PartialEq for UserData {
+    fn eq(&self, other: &UserData) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+}
+
+impl std::fmt::Display for UserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<{} {:p}>",
+            self.name,
+            Rc::as_ptr(&self.value) as *const ()
+        )
+    }
+}
+
+impl std::fmt::Debug for UserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// The values that can be used as map keys. Floats, lists and callables are
+/// left out as they have no reliable equality to hash or order by.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Bool(bool),
+    Int(i64),
+    Char(char),
+    String(String),
+}
+
+impl MapKey {
     #[inline(always)]
-    pub fn int(&self) -> i64 {
+    pub fn to_value(&self) -> VmBig {
         match self {
-            VmBig::Int(int) => *int,
-            _ => {
-                todo!()
+            MapKey::Bool(bool) => VmBig::Bool(*bool),
+            MapKey::Int(int) => VmBig::Int(*int),
+            MapKey::Char(c) => VmBig::Char(*c),
+            MapKey::String(string) => VmBig::String(Rc::new(string.clone())),
+        }
+    }
+}
+
+impl VmBig {
+    /// Name of the value's type as argument errors show it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            VmBig::Char(_) => "Char",
+            VmBig::Global(_) | VmBig::Register(_) => "Variable",
+            VmBig::Function(_) => "Function",
+            VmBig::Closure(..) => "Closure",
+            VmBig::Int(_) => "Int",
+            VmBig::Float(_) => "Float",
+            VmBig::Block(_) => "Block",
+            VmBig::Bool(_) => "Bool",
+            VmBig::List(_) => "List",
+            VmBig::String(_) => "String",
+            VmBig::Map(_) => "Map",
+            VmBig::UserData(data) => data.name(),
+            VmBig::None => "None",
+        }
+    }
+
+    /// Orders two values of comparable types. Ints and Floats compare by
+    /// value, Chars and Strings by their unicode scalar values, Bools as
+    /// false < true, and Lists lexicographically by their items. Returns
+    /// None for values that have no order, such as a String and an Int.
+    pub fn compare(&self, other: &VmBig) -> Option<Ordering> {
+        match (self, other) {
+            (VmBig::Int(a), VmBig::Int(b)) => Some(a.cmp(b)),
+            (VmBig::Int(a), VmBig::Float(b)) => (*a as f64).partial_cmp(b),
+            (VmBig::Float(a), VmBig::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (VmBig::Float(a), VmBig::Float(b)) => a.partial_cmp(b),
+            (VmBig::Char(a), VmBig::Char(b)) => Some(a.cmp(b)),
+            (VmBig::String(a), VmBig::String(b)) => Some(a.cmp(b)),
+            (VmBig::Bool(a), VmBig::Bool(b)) => Some(a.cmp(b)),
+            (VmBig::List(a), VmBig::List(b)) => {
+                for (x, y) in a.iter().zip(b.iter()) {
+                    match x.compare(y)? {
+                        Ordering::Equal => continue,
+                        ordering => return Some(ordering),
+                    }
+                }
+                Some(a.len().cmp(&b.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Equality as seen by Nova code. Numbers are equal across Int and Float
+    /// when their values are, Lists compare item by item, and values of
+    /// unrelated types are never equal.
+    pub fn equals(&self, other: &VmBig) -> bool {
+        match (self, other) {
+            (VmBig::Int(a), VmBig::Float(b)) => *a as f64 == *b,
+            (VmBig::Float(a), VmBig::Int(b)) => *a == *b as f64,
+            (VmBig::List(a), VmBig::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.equals(y))
+            }
+            (VmBig::Map(a), VmBig::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|((a, x), (b, y))| a == b && x.equals(y))
+            }
+            (VmBig::Closure(a, x), VmBig::Closure(b, y)) => {
+                a == b && x.len() == y.len() && x.iter().zip(y.iter()).all(|(x, y)| x.equals(y))
             }
+            (a, b) => a == b,
+        }
+    }
+
+    #[inline(always)]
+    pub fn to_key(self) -> Result<MapKey, NovaError> {
+        match self {
+            VmBig::Bool(bool) => Ok(MapKey::Bool(bool)),
+            VmBig::Int(int) => Ok(MapKey::Int(int)),
+            VmBig::Char(c) => Ok(MapKey::Char(c)),
+            VmBig::String(string) => Ok(MapKey::String(Rc::unwrap_or_clone(string))),
+            a => Err(common::error::runetime_error(format!(
+                "Map keys must be Bool, Int, Char or String, found {:?}",
+                a
+            ))),
+        }
+    }
+
+    #[inline(always)]
+    pub fn int(&self) -> Result<i64, NovaError> {
+        match self {
+            VmBig::Int(int) => Ok(*int),
+            a => Err(common::error::runetime_error(format!(
+                "Expected Int, found {:?}",
+                a
+            ))),
         }
     }
 
     #[inline(always)]
-    pub fn _to_data(&self) -> VmSmall {
+    pub fn _to_data(&self) -> Result<VmSmall, NovaError> {
         match self {
-            VmBig::Int(int) => VmSmall::Int(*int),
-            VmBig::Float(fl) => VmSmall::Float(*fl),
-            VmBig::Register(index) => VmSmall::Register(*index),
-            VmBig::Block(index) => VmSmall::Block(*index),
-            VmBig::Bool(bool) => VmSmall::Bool(*bool),
-            VmBig::List(_) => todo!(),
-            VmBig::None => VmSmall::None,
-            VmBig::Function(index) => VmSmall::Function(*index),
-            VmBig::Closure(_, _) => todo!(),
-            VmBig::String(_) => todo!(),
-            VmBig::Global(index) => VmSmall::Global(*index),
-            VmBig::Char(c) => VmSmall::Char(*c),
+            VmBig::Int(int) => Ok(VmSmall::Int(*int)),
+            VmBig::Float(fl) => Ok(VmSmall::Float(*fl)),
+            VmBig::Register(index) => Ok(VmSmall::Register(*index)),
+            VmBig::Block(index) => Ok(VmSmall::Block(*index)),
+            VmBig::Bool(bool) => Ok(VmSmall::Bool(*bool)),
+            VmBig::None => Ok(VmSmall::None),
+            VmBig::Function(index) => Ok(VmSmall::Function(*index)),
+            VmBig::Global(index) => Ok(VmSmall::Global(*index)),
+            VmBig::Char(c) => Ok(VmSmall::Char(*c)),
+            a => Err(common::error::runetime_error(format!(
+                "Cannot move {:?} off the heap",
+                a
+            ))),
         }
     }
 
     #[inline(always)]
-    pub fn _to_target(&self) -> usize {
+    pub fn _to_target(&self) -> Result<usize, NovaError> {
         match self {
-            VmBig::Int(_int) => todo!(),
-            VmBig::Float(_fl) => todo!(),
-            VmBig::Register(_index) => todo!(),
-            VmBig::Block(index) => *index,
-            VmBig::Bool(_) => todo!(),
-            VmBig::List(_) => todo!(),
-            VmBig::None => todo!(),
-            VmBig::Function(index) => *index,
-            VmBig::Closure(_, _) => todo!(),
-            VmBig::String(_) => todo!(),
-            VmBig::Global(index) => *index,
-            VmBig::Char(_) => todo!(),
+            VmBig::Block(index) => Ok(*index),
+            VmBig::Function(index) => Ok(*index),
+            VmBig::Global(index) => Ok(*index),
+            a => Err(common::error::runetime_error(format!(
+                "Expected Block, Function or Global, found {:?}",
+                a
+            ))),
         }
     }
 }
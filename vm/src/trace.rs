@@ -0,0 +1,118 @@
+use crate::state::VmBig;
+use common::{
+    code::{self, Code, OperandKind},
+    error::NovaError,
+};
+use std::{io::Write, ops::Range};
+
+pub fn new(output: Box<dyn Write>) -> Trace {
+    Trace {
+        opcodes: vec![],
+        range: None,
+        stack: 8,
+        output,
+    }
+}
+
+/// Logs every instruction the VM executes, one line each: offset, call
+/// depth, opcode, operand and the top of the stack before it runs.
+/// `opcodes` and `range` limit which instructions are logged, an empty
+/// list and None log everything. `stack` is how many values to show.
+pub struct Trace {
+    pub opcodes: Vec<u8>,
+    pub range: Option<Range<usize>>,
+    pub stack: usize,
+    output: Box<dyn Write>,
+}
+
+fn brief(value: &VmBig) -> String {
+    match value {
+        VmBig::Int(int) => int.to_string(),
+        VmBig::Float(float) => format!("{:?}", float),
+        VmBig::Bool(bool) => bool.to_string(),
+        VmBig::Char(c) => format!("{:?}", c),
+        VmBig::String(string) if string.chars().count() > 24 => {
+            format!("{:?}..", string.chars().take(24).collect::<String>())
+        }
+        VmBig::String(string) => format!("{:?}", string),
+        VmBig::List(list) => format!("list({})", list.len()),
+        VmBig::Map(map) => format!("map({})", map.len()),
+        VmBig::UserData(data) => data.to_string(),
+        VmBig::Closure(target, _) => format!("closure@{}", target),
+        VmBig::Function(target) => format!("function@{}", target),
+        VmBig::Block(target) => format!("block@{}", target),
+        VmBig::Register(index) => format!("register {}", index),
+        VmBig::Global(index) => format!("global {}", index),
+        VmBig::None => "None".to_string(),
+    }
+}
+
+// decodes the operand of the instruction at `offset` without running it
+fn operand(program: &[u8], offset: usize, natives: &[String]) -> String {
+    let mut bytes = program.iter().skip(offset + 1).copied();
+    let mut next = || bytes.next();
+    let opcode = program[offset];
+    let text = match code::operand_kind(opcode) {
+        OperandKind::None => Some(String::new()),
+        OperandKind::Uint if opcode == Code::NATIVE => code::read_uint(&mut next).map(|index| {
+            natives
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| index.to_string())
+        }),
+        OperandKind::Uint => code::read_uint(&mut next).map(|uint| uint.to_string()),
+        OperandKind::Jump => code::read_uint(&mut next).map(|jump| format!("+{}", jump)),
+        OperandKind::Int => code::read_int(&mut next).map(|int| int.to_string()),
+        OperandKind::Byte => next().map(|byte| byte.to_string()),
+        OperandKind::Float => code::read_float(&mut next).map(|float| format!("{:?}", float)),
+        OperandKind::Char => code::read_char(&mut next).map(|c| format!("{:?}", c)),
+        OperandKind::String => code::read_uint(&mut next).and_then(|len| {
+            let string: Vec<u8> = (0..len).map_while(|_| next()).collect();
+            Some(brief(&VmBig::String(std::rc::Rc::new(
+                String::from_utf8(string).ok()?,
+            ))))
+        }),
+    };
+    text.unwrap_or_else(|| "<malformed>".to_string())
+}
+
+impl Trace {
+    pub fn wants(&self, offset: usize, opcode: u8) -> bool {
+        (self.opcodes.is_empty() || self.opcodes.contains(&opcode))
+            && match &self.range {
+                Some(range) => range.contains(&offset),
+                None => true,
+            }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        program: &[u8],
+        offset: usize,
+        depth: usize,
+        natives: &[String],
+        (below, stack): (usize, Vec<VmBig>),
+    ) -> Result<(), NovaError> {
+        let opcode = program[offset];
+        let mut values: Vec<String> = stack.iter().map(brief).collect();
+        if below > 0 {
+            values.insert(0, format!("..{}", below))
+        }
+        writeln!(
+            self.output,
+            "{:06} {:>3} {:<14} {:<16} [{}]",
+            offset,
+            depth,
+            code::name(opcode).unwrap_or("UNKNOWN"),
+            operand(program, offset, natives),
+            values.join(", ")
+        )
+        .map_err(|error| common::error::file_error(format!("Could not write trace: {}", error)))
+    }
+
+    pub fn flush(&mut self) -> Result<(), NovaError> {
+        self.output
+            .flush()
+            .map_err(|error| common::error::file_error(format!("Could not write trace: {}", error)))
+    }
+}